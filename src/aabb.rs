@@ -0,0 +1,100 @@
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// relative rounding error of a slab distance, both ends of the overlap can be off by it
+// Pharr et al. - "Physically Based Rendering", 3rd edition, 3.9.2
+const SLAB_ERROR: f64 = 3. * (f64::EPSILON / 2.) / (1. - 3. * (f64::EPSILON / 2.));
+
+/// Axis-aligned bounding box. An empty box has min > max on every axis.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        Aabb {
+            min: Point3(a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+            max: Point3(a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+        }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point3(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    // for objects that extend forever, like planes
+    pub fn infinite() -> Aabb {
+        Aabb {
+            min: Point3(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            max: Point3(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3(
+                self.min.0.min(other.min.0),
+                self.min.1.min(other.min.1),
+                self.min.2.min(other.min.2),
+            ),
+            max: Point3(
+                self.max.0.max(other.max.0),
+                self.max.1.max(other.max.1),
+                self.max.2.max(other.max.2),
+            ),
+        }
+    }
+
+    pub fn include(&self, point: Point3) -> Aabb {
+        self.union(&Aabb {
+            min: point,
+            max: point,
+        })
+    }
+
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|axis| self.min[axis].is_finite() && self.max[axis].is_finite())
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.min + self.max) / 2.
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.extent();
+        if d.0 < 0. || d.1 < 0. || d.2 < 0. {
+            return 0.;
+        }
+        2. * (d.0 * d.1 + d.1 * d.2 + d.2 * d.0)
+    }
+
+    // slab test, returns the entry distance if the ray overlaps [t_min, t_max]
+    pub fn hit(&self, ray: &Ray, inv_direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        let mut t_enter = t_min;
+        let mut t_exit = t_max;
+
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            // f64::min/max ignore a NaN from 0 * inf, which keeps rays in the slab plane
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+        }
+
+        // a flat box (a disk in an axis plane) otherwise loses hits right at `t_max`
+        if t_enter <= t_exit + 2. * SLAB_ERROR * t_exit.abs() {
+            Some(t_enter)
+        } else {
+            None
+        }
+    }
+}
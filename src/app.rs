@@ -1,122 +1,86 @@
-use egui::{ColorImage, TextureHandle};
-use rand::distributions::{Distribution, Uniform};
-use rand::rngs::SmallRng;
-use rand::SeedableRng;
-use std::rc::Rc;
+use egui::TextureHandle;
+use std::path::Path;
+use std::sync::Arc;
 
 use crate::camera::Camera;
-use crate::ray::Ray;
-use crate::vec3::{Color, Point3, Vec3};
-use crate::world::{Dielectric, Lambertian, Metal, Sphere, World};
-
-struct Config {
-    max_depth: usize,
-    width: usize,
-    height: usize,
+use crate::cli::Options;
+use crate::environment::Sky;
+use crate::film::Film;
+use crate::output;
+use crate::render::{Config, Renderer};
+use crate::scene::Scene;
+use crate::tonemap::{ToneMapper, ToneMapping};
+use crate::world::World;
+
+struct SaveDialog {
+    open: bool,
+    path: String,
+    status: Option<String>,
 }
 
 pub struct App {
     render_texture: TextureHandle,
-    render_texture_img: ColorImage,
 
-    world: World,
+    world: Arc<World>,
     camera: Camera,
     config: Config,
+    // controls for the environment, when the scene uses a daylight sky
+    sky: Option<Sky>,
+    // only affects how the film is shown, so it never restarts the renderer
+    tone_mapping: ToneMapping,
 
-    rng: SmallRng,
-    aa_uniform_rng: Uniform<f64>,
+    renderer: Renderer,
+    film: Film,
 
-    sample_number: i32,
+    save_dialog: SaveDialog,
 }
 
 impl App {
     /// Called once before the first frame.
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, scene: Scene, options: &Options) -> Self {
         // This is also where you can customized the look at feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
 
         cc.egui_ctx.set_visuals(egui::Visuals::dark());
 
-        // rng
-        let rng = SmallRng::from_entropy();
-        let aa_uniform: Uniform<f64> = Uniform::from(0.0..1.0);
-
-        // image
-        let aspect_ratio = 16.0 / 9.0;
-        let width = 480;
-        let config = Config {
-            width,
-            height: (width as f64 / aspect_ratio) as usize,
-            max_depth: 50,
-        };
-
-        // Materials
-        let mat_ground = Rc::new(Lambertian {
-            albedo: Color(0.8, 0.8, 0.0),
-        });
-        let mat_left = Rc::new(Lambertian {
-            albedo: Color(0.7, 0.3, 0.2),
-        });
-        let mat_right = Rc::new(Metal {
-            albedo: Color(0.8, 0.6, 0.2),
-            fuzz: 1.0,
-        });
-        let mat_center = Rc::new(Dielectric {
-            ior: 1.5,
-            rng: SmallRng::from_entropy(),
-        });
-
-        // world
-        let mut world = World { objects: vec![] };
-        world.add(Box::new(Sphere {
-            center: Point3(0., -100.5, -1.),
-            radius: 100.,
-            material: mat_ground,
-        }));
-        world.add(Box::new(Sphere {
-            center: Point3(0., 0., -1.),
-            radius: 0.5,
-            material: mat_center,
-        }));
-        world.add(Box::new(Sphere {
-            center: Point3(1., 0., -1.),
-            radius: 0.5,
-            material: mat_right,
-        }));
-        world.add(Box::new(Sphere {
-            center: Point3(-1., 0., -1.),
-            radius: 0.5,
-            material: mat_left,
-        }));
-
-        // camera
-        let origin = Point3(-2., 2., 2.);
-        let camera = Camera::new(
-            origin,
-            (origin - Vec3(0., 0., -1.)).unit(),
-            Vec3(0., 1., 0.).unit(),
-            60.,
-            1.,
-            aspect_ratio,
-        );
+        let Scene {
+            world,
+            camera,
+            config,
+            sky,
+            tone_mapping,
+        } = scene;
 
         // render_texture
         let img = egui::ColorImage::new(
             [config.width, config.height],
             egui::Color32::from_rgb(255, 255, 255),
         );
-        let img_clone = img.clone();
         let tex_handle = cc.egui_ctx.load_texture("render", img);
 
+        // renderer
+        let world = Arc::new(world);
+        let renderer = Renderer::spawn(
+            world.clone(),
+            camera.clone(),
+            config.clone(),
+            cc.egui_ctx.clone(),
+        );
+
         App {
             render_texture: tex_handle,
-            render_texture_img: img_clone,
+            film: Film::new(config.width, config.height),
             world,
             camera,
             config,
-            rng,
-            aa_uniform_rng: aa_uniform,
-            sample_number: 0,
+            sky,
+            tone_mapping,
+            renderer,
+            save_dialog: SaveDialog {
+                open: false,
+                path: options.output.display().to_string(),
+                status: None,
+            },
         }
     }
 }
@@ -127,19 +91,25 @@ impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         let Self {
             render_texture,
-            render_texture_img,
             world,
             camera,
             config,
-            rng,
-            aa_uniform_rng,
-            sample_number,
+            sky,
+            tone_mapping,
+            renderer,
+            film,
+            save_dialog,
         } = self;
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("Save render…").clicked() {
+                        save_dialog.open = true;
+                        save_dialog.status = None;
+                        ui.close_menu();
+                    }
                     if ui.button("Quit").clicked() {
                         frame.quit();
                     }
@@ -147,47 +117,92 @@ impl eframe::App for App {
             });
         });
 
+        let SaveDialog { open, path, status } = save_dialog;
+        egui::Window::new("Save render")
+            .open(open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    ui.text_edit_singleline(path);
+                });
+                ui.label(".ppm and .png are tone mapped, .pfm and .hdr keep linear radiance");
+
+                if ui.button("Save").clicked() {
+                    let saved = output::write_image(film, tone_mapping, Path::new(path));
+                    *status = Some(match saved {
+                        Ok(()) => format!(
+                            "saved {:.1} samples per pixel to {}",
+                            film.mean_samples(),
+                            path
+                        ),
+                        Err(err) => format!("could not save: {}", err),
+                    });
+                }
+                if let Some(status) = status {
+                    ui.label(status.as_str());
+                }
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::warn_if_debug_build(ui);
 
             // update texture
-            if sample_number < &mut 100 {
-                for row in 0..config.height {
-                    for col in 0..config.width {
-                        let mut color = Color(0., 0., 0.);
-
-                        let u =
-                            (col as f64 + aa_uniform_rng.sample(rng)) / (config.width - 1) as f64;
-                        let v = ((config.height - row - 1) as f64 + aa_uniform_rng.sample(rng))
-                            / (config.height - 1) as f64;
-                        let ray = camera.ray_for(u, v);
-                        color += ray_color(&ray, world, config.max_depth);
-
-                        let pixel_idx = (row * config.width + col) as usize;
-                        render_texture_img.pixels[pixel_idx][0] = acc_color_channel(
-                            render_texture_img.pixels[pixel_idx][0] as f64 / 255.0,
-                            color.x(),
-                            *sample_number,
-                        );
-                        render_texture_img.pixels[pixel_idx][1] = acc_color_channel(
-                            render_texture_img.pixels[pixel_idx][1] as f64 / 255.0,
-                            color.y(),
-                            *sample_number,
-                        );
-                        render_texture_img.pixels[pixel_idx][2] = acc_color_channel(
-                            render_texture_img.pixels[pixel_idx][2] as f64 / 255.0,
-                            color.z(),
-                            *sample_number,
-                        );
-                    }
-                }
-                render_texture.set(render_texture_img.clone());
-                *sample_number += 1;
+            if let Some(pass) = renderer.latest_pass() {
+                *film = pass.film;
+                render_texture.set(film.to_color_image(tone_mapping));
             }
 
             // draw
             ui.image(render_texture.id(), render_texture.size_vec2());
-            ui.heading(format!("Samples: {}", sample_number));
+            ui.heading(format!(
+                "Passes: {}, {:.1} samples per pixel",
+                film.passes,
+                film.mean_samples()
+            ));
+
+            let mut tone_mapping_changed = false;
+
+            ui.horizontal(|ui| {
+                ui.label("Tone mapper:");
+                egui::ComboBox::from_id_source("tone_mapper")
+                    .selected_text(tone_mapping.tone_mapper.to_string())
+                    .show_ui(ui, |ui| {
+                        for tone_mapper in ToneMapper::ALL {
+                            let option = ui.selectable_value(
+                                &mut tone_mapping.tone_mapper,
+                                tone_mapper,
+                                tone_mapper.to_string(),
+                            );
+                            tone_mapping_changed |= option.changed();
+                        }
+                    });
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Exposure:");
+                let exposure_slider =
+                    egui::Slider::new(&mut tone_mapping.exposure, -10.0..=10.0).step_by(0.1);
+                tone_mapping_changed |= ui.add(exposure_slider).changed();
+            });
+
+            if tone_mapping.tone_mapper == ToneMapper::ExtendedReinhard {
+                ui.horizontal(|ui| {
+                    ui.label("White point:");
+                    let white_slider =
+                        egui::Slider::new(&mut tone_mapping.white_point, 0.1..=100.0)
+                            .logarithmic(true);
+                    tone_mapping_changed |= ui.add(white_slider).changed();
+                });
+            }
+
+            // the film is untouched, only its conversion for display changes
+            if tone_mapping_changed {
+                render_texture.set(film.to_color_image(tone_mapping));
+            }
+
+            let mut camera_changed = false;
 
             ui.horizontal(|ui| {
                 ui.label("Origin:");
@@ -195,10 +210,7 @@ impl eframe::App for App {
                 let y_input = ui.add(egui::DragValue::new(&mut camera.origin.1).speed(0.2));
                 let z_input = ui.add(egui::DragValue::new(&mut camera.origin.2).speed(0.2));
 
-                if x_input.changed() || y_input.changed() || z_input.changed() {
-                    camera.update();
-                    *sample_number = 0;
-                }
+                camera_changed |= x_input.changed() || y_input.changed() || z_input.changed();
             });
 
             ui.horizontal(|ui| {
@@ -207,59 +219,78 @@ impl eframe::App for App {
                 let y_input = ui.add(egui::DragValue::new(&mut camera.direction.1).speed(0.2));
                 let z_input = ui.add(egui::DragValue::new(&mut camera.direction.2).speed(0.2));
 
-                if x_input.changed() || y_input.changed() || z_input.changed() {
-                    camera.update();
-                    *sample_number = 0;
-                }
+                camera_changed |= x_input.changed() || y_input.changed() || z_input.changed();
             });
 
             ui.horizontal(|ui| {
                 ui.label("V fov:");
                 let vfov_slider = egui::Slider::new(&mut camera.vfov, 0.0..=100.0);
-                if ui.add(vfov_slider).changed() {
-                    camera.update();
-                    *sample_number = 0;
-                }
+                camera_changed |= ui.add(vfov_slider).changed();
             });
 
             ui.horizontal(|ui| {
                 ui.label("Focal length:");
                 let vfov_slider =
-                    egui::Slider::new(&mut camera.focal_length, 0.0..=20.0).step_by(0.05);
-                if ui.add(vfov_slider).changed() {
-                    camera.update();
-                    *sample_number = 0;
-                }
+                    egui::Slider::new(&mut camera.focal_length, 0.05..=20.0).step_by(0.05);
+                camera_changed |= ui.add(vfov_slider).changed();
             });
-        });
 
-        ctx.request_repaint();
-    }
-}
+            ui.horizontal(|ui| {
+                ui.label("Aperture:");
+                let aperture_slider =
+                    egui::Slider::new(&mut camera.aperture, 0.0..=2.0).step_by(0.01);
+                camera_changed |= ui.add(aperture_slider).changed();
+            });
 
-fn ray_color(ray: &Ray, world: &World, depth: usize) -> Color {
-    if depth == 0 {
-        return Color(0., 0., 0.);
-    }
-    if let Some(hit_record) = world.hit(ray, 0.001, f64::INFINITY) {
-        match (*hit_record.material).scatter(ray, &hit_record) {
-            Some(scatter_record) => {
-                return scatter_record.attenuation
-                    * ray_color(&scatter_record.ray, world, depth - 1)
-            }
-            None => return Color(0., 0., 0.),
-        }
-    }
+            ui.horizontal(|ui| {
+                ui.label("Focus distance:");
+                let focus_slider =
+                    egui::Slider::new(&mut camera.focus_distance, 0.1..=100.0).logarithmic(true);
+                camera_changed |= ui.add(focus_slider).changed();
+            });
 
-    let unit_direction = ray.direction.unit();
-    let t = 0.5 * (unit_direction.y() + 1.);
-    (1. - t) * Color(1., 1., 1.) + t * Color(0.5, 0.7, 1.)
-}
+            let mut sky_changed = false;
+
+            if let Some(sky) = sky {
+                ui.horizontal(|ui| {
+                    ui.label("Sun elevation:");
+                    let elevation_slider = egui::Slider::new(&mut sky.elevation, 0.0..=90.0);
+                    sky_changed |= ui.add(elevation_slider).changed();
+                });
 
-// moving average + gamma correction
-fn acc_color_channel(acc: f64, new_val: f64, iteration: i32) -> u8 {
-    let acc_component = iteration as f64 * acc * acc;
-    let new_average = (new_val + acc_component) / (iteration + 1) as f64;
+                ui.horizontal(|ui| {
+                    ui.label("Sun azimuth:");
+                    let azimuth_slider = egui::Slider::new(&mut sky.azimuth, -180.0..=180.0);
+                    sky_changed |= ui.add(azimuth_slider).changed();
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Turbidity:");
+                    let turbidity_slider =
+                        egui::Slider::new(&mut sky.turbidity, 2.0..=10.0).step_by(0.1);
+                    sky_changed |= ui.add(turbidity_slider).changed();
+                });
 
-    (new_average.sqrt() * 255.0) as u8
+                if sky_changed {
+                    sky.update();
+                    // the world can only change once the worker has let go of it
+                    renderer.stop();
+                    Arc::get_mut(world)
+                        .expect("a stopped renderer doesn't share the world")
+                        .set_environment(Arc::new(sky.clone()));
+                }
+            }
+
+            if camera_changed {
+                camera.update();
+            }
+
+            // restart the worker so it never mixes samples from two scene states
+            if camera_changed || sky_changed {
+                *renderer =
+                    Renderer::spawn(world.clone(), camera.clone(), config.clone(), ctx.clone());
+                *film = Film::new(config.width, config.height);
+            }
+        });
+    }
 }
@@ -0,0 +1,432 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use crate::world::{HitRecord, Hittable};
+
+// number of candidate split planes per axis
+const BIN_COUNT: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+// cost of visiting a node, relative to intersecting one primitive
+const TRAVERSAL_COST: f64 = 0.125;
+
+struct Node {
+    bounds: Aabb,
+    // leaf: first entry in `indices`, interior: index of the second child
+    // (the first child always directly follows its parent)
+    offset: usize,
+    // primitives in a leaf, 0 for interior nodes
+    count: usize,
+    axis: usize,
+}
+
+/// Result of intersecting one primitive, ordered by distance along the ray.
+pub trait Intersection {
+    fn t(&self) -> f64;
+}
+
+impl Intersection for HitRecord {
+    fn t(&self) -> f64 {
+        self.t
+    }
+}
+
+// a bare distance, for queries that don't need a full hit record
+impl Intersection for f64 {
+    fn t(&self) -> f64 {
+        *self
+    }
+}
+
+/// Bounding volume hierarchy over primitive indices, built with a binned
+/// surface area heuristic. It only knows about boxes, the caller intersects
+/// the primitives themselves, which lets both `Bvh` and meshes share it.
+pub struct BvhTree {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl BvhTree {
+    pub fn build(bounds: &[Aabb]) -> BvhTree {
+        let centroids: Vec<Point3> = bounds.iter().map(Aabb::centroid).collect();
+        let mut indices: Vec<usize> = (0..bounds.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * bounds.len());
+
+        if !bounds.is_empty() {
+            build_node(bounds, &centroids, &mut indices, 0, &mut nodes);
+        }
+
+        BvhTree { nodes, indices }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or_else(Aabb::empty, |root| root.bounds)
+    }
+
+    /// Finds the closest primitive hit. `hit_primitive` is called with a primitive
+    /// index and the current closest distance. Ties go to the higher index, so the
+    /// result matches testing every primitive in order.
+    pub fn hit<T, F>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut hit_primitive: F,
+    ) -> Option<(usize, T)>
+    where
+        T: Intersection,
+        F: FnMut(usize, f64) -> Option<T>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_direction = Vec3(
+            1. / ray.direction.0,
+            1. / ray.direction.1,
+            1. / ray.direction.2,
+        );
+        let mut closest: Option<(usize, T)> = None;
+        let mut closest_so_far = t_max;
+
+        let mut stack = Vec::with_capacity(32);
+        stack.push(0);
+
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            if node
+                .bounds
+                .hit(ray, &inv_direction, t_min, closest_so_far)
+                .is_none()
+            {
+                continue;
+            }
+
+            if node.count > 0 {
+                for &primitive in &self.indices[node.offset..node.offset + node.count] {
+                    if let Some(rec) = hit_primitive(primitive, closest_so_far) {
+                        let closer = match &closest {
+                            None => true,
+                            Some((closest_idx, closest_rec)) => {
+                                rec.t() < closest_rec.t()
+                                    || (rec.t() == closest_rec.t() && primitive > *closest_idx)
+                            }
+                        };
+                        if closer {
+                            closest_so_far = rec.t();
+                            closest = Some((primitive, rec));
+                        }
+                    }
+                }
+            } else if ray.direction[node.axis] < 0. {
+                // visit the child nearer to the ray origin first
+                stack.push(node_idx + 1);
+                stack.push(node.offset);
+            } else {
+                stack.push(node.offset);
+                stack.push(node_idx + 1);
+            }
+        }
+
+        closest
+    }
+}
+
+fn build_node(
+    bounds: &[Aabb],
+    centroids: &[Point3],
+    indices: &mut [usize],
+    offset: usize,
+    nodes: &mut Vec<Node>,
+) {
+    let node_bounds = indices
+        .iter()
+        .fold(Aabb::empty(), |acc, &i| acc.union(&bounds[i]));
+    let node_idx = nodes.len();
+    nodes.push(Node {
+        bounds: node_bounds,
+        offset,
+        count: indices.len(),
+        axis: 0,
+    });
+    if indices.len() == 1 {
+        return;
+    }
+
+    let centroid_bounds = indices
+        .iter()
+        .fold(Aabb::empty(), |acc, &i| acc.include(centroids[i]));
+    let split = best_split(bounds, centroids, indices, &node_bounds, &centroid_bounds);
+
+    let (axis, mid) = match split {
+        Some((axis, split_bin, cost)) => {
+            if indices.len() <= MAX_LEAF_SIZE && cost >= indices.len() as f64 {
+                return;
+            }
+            let mid = partition(indices, |&i| {
+                bin_for(centroids[i][axis], &centroid_bounds, axis) < split_bin
+            });
+            (axis, mid)
+        }
+        // every centroid is in the same spot, binning can't separate them
+        None if indices.len() <= MAX_LEAF_SIZE => return,
+        None => (0, indices.len() / 2),
+    };
+    let mid = if mid == 0 || mid == indices.len() {
+        indices.len() / 2
+    } else {
+        mid
+    };
+
+    let (left, right) = indices.split_at_mut(mid);
+    build_node(bounds, centroids, left, offset, nodes);
+    let second_child = nodes.len();
+    build_node(bounds, centroids, right, offset + mid, nodes);
+
+    let node = &mut nodes[node_idx];
+    node.offset = second_child;
+    node.count = 0;
+    node.axis = axis;
+}
+
+// returns (axis, first bin of the right side, sah cost) of the cheapest split
+fn best_split(
+    bounds: &[Aabb],
+    centroids: &[Point3],
+    indices: &[usize],
+    node_bounds: &Aabb,
+    centroid_bounds: &Aabb,
+) -> Option<(usize, usize, f64)> {
+    let node_area = node_bounds.surface_area();
+    let mut best: Option<(usize, usize, f64)> = None;
+
+    for axis in 0..3 {
+        if centroid_bounds.extent()[axis] <= 0. {
+            continue;
+        }
+
+        let mut bins = [(Aabb::empty(), 0usize); BIN_COUNT];
+        for &i in indices {
+            let bin = &mut bins[bin_for(centroids[i][axis], centroid_bounds, axis)];
+            bin.0 = bin.0.union(&bounds[i]);
+            bin.1 += 1;
+        }
+
+        // sweep from the right so each split can read its right-hand side
+        let mut right_area = [0.; BIN_COUNT];
+        let mut right_count = [0; BIN_COUNT];
+        let mut acc = (Aabb::empty(), 0);
+        for bin in (1..BIN_COUNT).rev() {
+            acc = (acc.0.union(&bins[bin].0), acc.1 + bins[bin].1);
+            right_area[bin] = acc.0.surface_area();
+            right_count[bin] = acc.1;
+        }
+
+        let mut left = (Aabb::empty(), 0);
+        for split_bin in 1..BIN_COUNT {
+            left = (
+                left.0.union(&bins[split_bin - 1].0),
+                left.1 + bins[split_bin - 1].1,
+            );
+            if left.1 == 0 || right_count[split_bin] == 0 {
+                continue;
+            }
+
+            let cost = TRAVERSAL_COST
+                + (left.0.surface_area() * left.1 as f64
+                    + right_area[split_bin] * right_count[split_bin] as f64)
+                    / node_area;
+            if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                best = Some((axis, split_bin, cost));
+            }
+        }
+    }
+
+    best
+}
+
+fn bin_for(centroid: f64, centroid_bounds: &Aabb, axis: usize) -> usize {
+    let relative = (centroid - centroid_bounds.min[axis]) / centroid_bounds.extent()[axis];
+    ((relative * BIN_COUNT as f64) as usize).min(BIN_COUNT - 1)
+}
+
+// moves everything matching `pred` to the front, returns how many matched
+fn partition<F: Fn(&usize) -> bool>(indices: &mut [usize], pred: F) -> usize {
+    let mut mid = 0;
+    for i in 0..indices.len() {
+        if pred(&indices[i]) {
+            indices.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+// Bvh
+
+/// A list of hittables behind a `BvhTree`. Objects without finite bounds,
+/// like planes, can't go in the tree and are tested one by one instead.
+pub struct Bvh {
+    objects: Vec<Arc<dyn Hittable>>,
+    tree: BvhTree,
+    // indices into `objects`, in their original order
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Bvh {
+        let (bounded, unbounded): (Vec<usize>, Vec<usize>) =
+            (0..objects.len()).partition(|&i| objects[i].bounding_box().is_finite());
+        let bounds: Vec<Aabb> = bounded.iter().map(|&i| objects[i].bounding_box()).collect();
+
+        Bvh {
+            tree: BvhTree::build(&bounds),
+            objects,
+            bounded,
+            unbounded,
+        }
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest: Option<(usize, HitRecord)> = None;
+        let mut closest_so_far = t_max;
+
+        for &object_idx in &self.unbounded {
+            if let Some(rec) = self.objects[object_idx].hit(ray, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                closest = Some((object_idx, rec));
+            }
+        }
+
+        let tree_hit = self.tree.hit(ray, t_min, closest_so_far, |i, t_max| {
+            self.objects[self.bounded[i]].hit(ray, t_min, t_max)
+        });
+        if let Some((i, rec)) = tree_hit {
+            let object_idx = self.bounded[i];
+            let closer = match &closest {
+                None => true,
+                Some((closest_idx, closest_rec)) => {
+                    rec.t < closest_rec.t || (rec.t == closest_rec.t && object_idx > *closest_idx)
+                }
+            };
+            if closer {
+                closest = Some((object_idx, rec));
+            }
+        }
+
+        closest.map(|(_, rec)| rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self.unbounded.is_empty() {
+            true => self.tree.bounding_box(),
+            false => Aabb::infinite(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::texture::SolidColor;
+    use crate::world::{Disk, Lambertian, Material, Plane, Sphere};
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian {
+            albedo: Arc::new(SolidColor {
+                color: Vec3(0.5, 0.5, 0.5),
+            }),
+        })
+    }
+
+    // what the tree has to agree with: every object in order, later ones winning ties
+    fn linear_hit(objects: &[Arc<dyn Hittable>], ray: &Ray) -> Option<HitRecord> {
+        let mut closest = None;
+        let mut closest_so_far = f64::INFINITY;
+        for object in objects {
+            if let Some(rec) = object.hit(ray, 0.001, closest_so_far) {
+                closest_so_far = rec.t;
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    #[test]
+    fn matches_linear_scan() {
+        let mut rng = SmallRng::seed_from_u64(7);
+        let random_point = |rng: &mut SmallRng, extent: f64| {
+            Vec3(
+                rng.gen_range(-extent..extent),
+                rng.gen_range(-extent..extent),
+                rng.gen_range(-extent..extent),
+            )
+        };
+
+        let mut objects: Vec<Arc<dyn Hittable>> = vec![];
+        for _ in 0..200 {
+            let center = random_point(&mut rng, 10.);
+            let radius = rng.gen_range(0.1..1.5);
+            // a copy of some spheres right behind them in the list, so hits tie
+            let copies = if rng.gen_bool(0.2) { 2 } else { 1 };
+            for _ in 0..copies {
+                objects.push(Arc::new(Sphere {
+                    center,
+                    radius,
+                    material: material(),
+                }));
+            }
+        }
+        // unbounded planes take the other path through `Bvh::hit`, coplanar
+        // disks on either side of them in the list tie with them from the tree
+        let plane = || -> Arc<dyn Hittable> {
+            Arc::new(Plane {
+                point: Vec3(0., -8., 0.),
+                normal: Vec3(0., 1., 0.),
+                material: material(),
+            })
+        };
+        let disk = || -> Arc<dyn Hittable> {
+            Arc::new(Disk {
+                center: Vec3(0., -8., 0.),
+                normal: Vec3(0., 1., 0.),
+                radius: 6.,
+                material: material(),
+            })
+        };
+        objects.splice(0..0, [plane(), disk(), plane()]);
+        objects.extend([disk(), plane(), disk()]);
+
+        let bvh = Bvh::new(objects.clone());
+        for _ in 0..10_000 {
+            let ray = Ray {
+                origin: random_point(&mut rng, 12.),
+                direction: random_point(&mut rng, 1.),
+                time: 0.,
+            };
+            let expected = linear_hit(&objects, &ray);
+            let actual = bvh.hit(&ray, 0.001, f64::INFINITY);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    assert_eq!(expected.t, actual.t);
+                    assert!(Arc::ptr_eq(&expected.material, &actual.material));
+                }
+                (expected, actual) => panic!(
+                    "linear scan hit: {}, bvh hit: {}",
+                    expected.is_some(),
+                    actual.is_some()
+                ),
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::f64::consts::PI;
 
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::{Point3, Vec3};
 
+#[derive(Clone)]
 pub struct Camera {
     pub origin: Point3,
     pub direction: Vec3,
@@ -10,7 +12,17 @@ pub struct Camera {
     pub vfov: f64,
     pub focal_length: f64,
     pub aspect_ratio: f64,
+    // lens diameter, 0 is a pinhole with everything in focus
+    pub aperture: f64,
+    // distance from `origin` to the plane that is in perfect focus
+    pub focus_distance: f64,
+    // rays get times spread evenly over [shutter_open, shutter_close]
+    pub shutter_open: f64,
+    pub shutter_close: f64,
 
+    // unit vectors spanning the lens
+    lens_u: Vec3,
+    lens_v: Vec3,
     horizontal_vector: Vec3,
     vertical_vector: Vec3,
     lower_left_corner: Vec3,
@@ -32,7 +44,14 @@ impl Camera {
             vfov,
             focal_length,
             aspect_ratio,
+            // a pinhole until told otherwise
+            aperture: 0.,
+            focus_distance: focal_length,
+            shutter_open: 0.,
+            shutter_close: 0.,
 
+            lens_u: Vec3(0., 0., 0.),
+            lens_v: Vec3(0., 0., 0.),
             horizontal_vector: Vec3(0., 0., 0.),
             vertical_vector: Vec3(0., 0., 0.),
             lower_left_corner: Vec3(0., 0., 0.),
@@ -42,14 +61,36 @@ impl Camera {
         cam
     }
 
-    pub fn ray_for(&self, u: f64, v: f64) -> Ray {
+    // the lens position and time come from `sampler`, when they matter
+    pub fn ray_for(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Ray {
+        let direction =
+            self.lower_left_corner + u * self.horizontal_vector + v * self.vertical_vector
+                - self.origin;
+        let lens = match self.aperture > 0. {
+            true => self.aperture / 2. * Vec3::sample_in_unit_disk(sampler.get_2d()),
+            false => Vec3(0., 0., 0.),
+        };
+        let time = match self.shutter_close > self.shutter_open {
+            true => self.shutter_open + sampler.get_1d() * (self.shutter_close - self.shutter_open),
+            false => self.shutter_open,
+        };
+        if self.aperture <= 0. {
+            return Ray {
+                origin: self.origin,
+                direction: direction.unit(),
+                time,
+            };
+        }
+
+        // thin lens: rays through every point of the lens meet again on the focus plane,
+        // `focus_distance` along the view axis (`self.direction` points the other way)
+        let depth = -direction.dot(&self.direction);
+        let focus_point = self.origin + direction * (self.focus_distance / depth);
+        let origin = self.origin + lens.x() * self.lens_u + lens.y() * self.lens_v;
         Ray {
-            origin: self.origin,
-            direction: (self.lower_left_corner
-                + u * self.horizontal_vector
-                + v * self.vertical_vector
-                - self.origin)
-                .unit(),
+            origin,
+            direction: (focus_point - origin).unit(),
+            time,
         }
     }
 
@@ -64,6 +105,8 @@ impl Camera {
 
         let u = self.vup.cross(&self.direction).unit();
         let v = self.direction.cross(&u);
+        self.lens_u = u;
+        self.lens_v = v;
 
         self.horizontal_vector = viewport_width * u;
         self.vertical_vector = viewport_height * v;
@@ -0,0 +1,258 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::sampler::SamplerKind;
+use crate::scene::Scene;
+use crate::tonemap::ToneMapper;
+use crate::vec3::Vec3;
+
+pub const USAGE: &str = "\
+usage: ray-tracing-in-one-weekend [options]
+
+Opens the interactive viewer, or renders straight to a file with --headless.
+Image and camera options override the values from the scene file.
+
+options:
+    --scene <path>          scene file to render (default: built-in demo scene)
+    --headless              render without a window and write the image to --output
+    --width <px>            image width
+    --height <px>           image height (default: keeps the scene's aspect ratio)
+    --samples <n>           samples per pixel, the most any pixel gets
+    --noise-threshold <e>   stop sampling pixels once their relative error is below e
+    --time-budget <s>       stop rendering after about this many seconds
+    --max-depth <n>         maximum bounces per path
+    --sampler <name>        independent, stratified, halton or sobol
+    --seed <n>              picks the noise pattern, the same seed gives the same image
+    --origin <x,y,z>        camera origin
+    --direction <x,y,z>     camera direction
+    --vfov <degrees>        camera vertical field of view
+    --focal-length <f>      camera focal length
+    --aperture <d>          camera lens diameter, 0 keeps everything in focus
+    --focus-distance <d>    distance from the camera to the plane in focus
+    --tone-mapper <name>    clamp, reinhard, extended-reinhard, aces or agx
+    --exposure <stops>      brightens (or darkens, when negative) the image
+    --output <path>         output image, .ppm .png .pfm or .hdr (default out.ppm)
+    -h, --help              print this message
+";
+
+pub struct Options {
+    pub help: bool,
+    pub headless: bool,
+    pub scene: Option<PathBuf>,
+
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub samples: Option<i32>,
+    pub noise_threshold: Option<f64>,
+    pub time_budget: Option<f64>,
+    pub max_depth: Option<usize>,
+    pub sampler: Option<SamplerKind>,
+    pub seed: Option<u64>,
+
+    pub origin: Option<Vec3>,
+    pub direction: Option<Vec3>,
+    pub vfov: Option<f64>,
+    pub focal_length: Option<f64>,
+    pub aperture: Option<f64>,
+    pub focus_distance: Option<f64>,
+
+    pub tone_mapper: Option<ToneMapper>,
+    pub exposure: Option<f64>,
+
+    pub output: PathBuf,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            help: false,
+            headless: false,
+            scene: None,
+            width: None,
+            height: None,
+            samples: None,
+            noise_threshold: None,
+            time_budget: None,
+            max_depth: None,
+            sampler: None,
+            seed: None,
+            origin: None,
+            direction: None,
+            vfov: None,
+            focal_length: None,
+            aperture: None,
+            focus_distance: None,
+            tone_mapper: None,
+            exposure: None,
+            output: PathBuf::from("out.ppm"),
+        }
+    }
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--headless" => options.headless = true,
+                _ => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("missing value for {}", flag))?;
+                    match flag.as_str() {
+                        "--scene" => options.scene = Some(PathBuf::from(value)),
+                        "--width" => options.width = Some(parse_value(&flag, &value)?),
+                        "--height" => options.height = Some(parse_value(&flag, &value)?),
+                        "--samples" => options.samples = Some(parse_value(&flag, &value)?),
+                        "--noise-threshold" => {
+                            options.noise_threshold = Some(parse_value(&flag, &value)?)
+                        }
+                        "--time-budget" => options.time_budget = Some(parse_value(&flag, &value)?),
+                        "--max-depth" => options.max_depth = Some(parse_value(&flag, &value)?),
+                        "--sampler" => options.sampler = Some(parse_value(&flag, &value)?),
+                        "--seed" => options.seed = Some(parse_value(&flag, &value)?),
+                        "--origin" => options.origin = Some(parse_vec3(&flag, &value)?),
+                        "--direction" => options.direction = Some(parse_vec3(&flag, &value)?),
+                        "--vfov" => options.vfov = Some(parse_value(&flag, &value)?),
+                        "--focal-length" => {
+                            options.focal_length = Some(parse_value(&flag, &value)?)
+                        }
+                        "--aperture" => options.aperture = Some(parse_value(&flag, &value)?),
+                        "--focus-distance" => {
+                            options.focus_distance = Some(parse_value(&flag, &value)?)
+                        }
+                        "--tone-mapper" => options.tone_mapper = Some(parse_value(&flag, &value)?),
+                        "--exposure" => options.exposure = Some(parse_value(&flag, &value)?),
+                        "--output" => options.output = PathBuf::from(value),
+                        _ => return Err(format!("unknown option {}", flag)),
+                    }
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    // overrides whatever the scene file set up
+    pub fn apply(&self, scene: &mut Scene) -> Result<(), String> {
+        let config = &mut scene.config;
+        // whichever of width and height is missing follows the scene's aspect ratio
+        let aspect_ratio = config.width as f64 / config.height as f64;
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => {
+                config.width = width;
+                config.height = height;
+            }
+            (Some(width), None) => {
+                config.width = width;
+                config.height = (width as f64 / aspect_ratio) as usize;
+            }
+            (None, Some(height)) => {
+                config.width = (height as f64 * aspect_ratio) as usize;
+                config.height = height;
+            }
+            (None, None) => {}
+        }
+        if let Some(samples) = self.samples {
+            if samples < 1 {
+                return Err("--samples must be at least 1".to_string());
+            }
+            config.max_samples = samples;
+        }
+        if let Some(noise_threshold) = self.noise_threshold {
+            if noise_threshold <= 0. {
+                return Err("--noise-threshold must be positive".to_string());
+            }
+            config.noise_threshold = Some(noise_threshold);
+        }
+        if let Some(time_budget) = self.time_budget {
+            if time_budget <= 0. || !time_budget.is_finite() {
+                return Err("--time-budget must be a positive number of seconds".to_string());
+            }
+            config.time_budget = Some(Duration::from_secs_f64(time_budget));
+        }
+        // no pixel's noise estimate would ever be trusted
+        if config.is_adaptive() && config.min_samples > config.max_samples {
+            return Err(format!(
+                "--samples must be at least the scene's min_samples ({}) when sampling adaptively",
+                config.min_samples
+            ));
+        }
+        if let Some(max_depth) = self.max_depth {
+            config.max_depth = max_depth;
+        }
+        if let Some(sampler) = self.sampler {
+            config.sampler = sampler;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = seed;
+        }
+        // uv mapping in the renderer divides by (size - 1)
+        if config.width < 2 || config.height < 2 {
+            return Err("image must be at least 2x2 pixels".to_string());
+        }
+
+        let camera = &mut scene.camera;
+        camera.aspect_ratio = config.width as f64 / config.height as f64;
+        if let Some(origin) = self.origin {
+            camera.origin = origin;
+        }
+        if let Some(direction) = self.direction {
+            camera.direction = direction;
+        }
+        if let Some(vfov) = self.vfov {
+            camera.vfov = vfov;
+        }
+        if let Some(focal_length) = self.focal_length {
+            if focal_length <= 0. {
+                return Err("--focal-length must be positive".to_string());
+            }
+            camera.focal_length = focal_length;
+        }
+        if let Some(aperture) = self.aperture {
+            if aperture < 0. {
+                return Err("--aperture must not be negative".to_string());
+            }
+            camera.aperture = aperture;
+        }
+        if let Some(focus_distance) = self.focus_distance {
+            if focus_distance <= 0. {
+                return Err("--focus-distance must be positive".to_string());
+            }
+            camera.focus_distance = focus_distance;
+        }
+        camera.update();
+
+        let tone_mapping = &mut scene.tone_mapping;
+        if let Some(tone_mapper) = self.tone_mapper {
+            tone_mapping.tone_mapper = tone_mapper;
+        }
+        if let Some(exposure) = self.exposure {
+            tone_mapping.exposure = exposure;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+fn parse_vec3(flag: &str, value: &str) -> Result<Vec3, String> {
+    let components = value
+        .split(',')
+        .map(|component| parse_value(flag, component.trim()))
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    match components[..] {
+        [x, y, z] => Ok(Vec3(x, y, z)),
+        _ => Err(format!("expected x,y,z for {}, got '{}'", flag, value)),
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::vec3::Point3;
+use crate::world::{FaceKind, HitRecord, Hittable, Interval};
+
+// Constructive solid geometry
+// Roth - "Ray Casting for Modeling Solids", 1982
+
+#[derive(Clone, Copy)]
+pub enum Operation {
+    Union,
+    Intersection,
+    // `left` with `right` cut out of it
+    Difference,
+}
+
+impl Operation {
+    fn contains(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Operation::Union => in_left || in_right,
+            Operation::Intersection => in_left && in_right,
+            Operation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// A solid made from two solids, see `Hittable::is_solid`. Its own surface is
+/// whatever parts of theirs border the combined volume, keeping their materials.
+pub struct Csg {
+    operation: Operation,
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bounding_box: Aabb,
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Csg {
+        let (left_box, right_box) = (left.bounding_box(), right.bounding_box());
+        let bounding_box = match operation {
+            Operation::Union => left_box.union(&right_box),
+            Operation::Intersection => Aabb {
+                min: Point3(
+                    left_box.min.0.max(right_box.min.0),
+                    left_box.min.1.max(right_box.min.1),
+                    left_box.min.2.max(right_box.min.2),
+                ),
+                max: Point3(
+                    left_box.max.0.min(right_box.max.0),
+                    left_box.max.1.min(right_box.max.1),
+                    left_box.max.2.min(right_box.max.2),
+                ),
+            },
+            Operation::Difference => left_box,
+        };
+
+        Csg {
+            operation,
+            left,
+            right,
+            bounding_box,
+        }
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.intervals(ray)
+            .into_iter()
+            .flat_map(|interval| [interval.enter, interval.exit])
+            .find(|hit_record| hit_record.t >= t_min && hit_record.t <= t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    // walks the boundaries of both operands in order, keeping the ones where
+    // being inside the combination changes
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut boundaries: Vec<(HitRecord, bool)> = vec![];
+        for (intervals, is_left) in [
+            (self.left.intervals(ray), true),
+            (self.right.intervals(ray), false),
+        ] {
+            for interval in intervals {
+                boundaries.push((interval.enter, is_left));
+                boundaries.push((interval.exit, is_left));
+            }
+        }
+        // stable, so an interval's own enter stays ahead of its exit on ties
+        boundaries.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+        let mut intervals = vec![];
+        let (mut in_left, mut in_right) = (false, false);
+        let mut enter: Option<HitRecord> = None;
+        for (mut hit_record, is_left) in boundaries {
+            let was_inside = self.operation.contains(in_left, in_right);
+            match is_left {
+                true => in_left = !in_left,
+                false => in_right = !in_right,
+            }
+            let inside = self.operation.contains(in_left, in_right);
+
+            // a subtracted surface is entered from its back, so the face is
+            // decided by the combination rather than the operand
+            match (was_inside, inside) {
+                (false, true) => {
+                    hit_record.face = FaceKind::Front;
+                    enter = Some(hit_record);
+                }
+                (true, false) => {
+                    hit_record.face = FaceKind::Back;
+                    if let Some(enter) = enter.take() {
+                        intervals.push(Interval {
+                            enter,
+                            exit: hit_record,
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        intervals
+    }
+}
@@ -0,0 +1,382 @@
+use std::f64::consts::PI;
+
+use crate::image::Image;
+use crate::sampler::Sampler;
+use crate::vec3::{Color, Vec3};
+
+/// Light arriving from infinitely far away, seen by rays that leave the scene.
+pub trait Environment: Send + Sync {
+    fn radiance(&self, direction: &Vec3) -> Color;
+
+    // environments that are worth sampling directly, like a map with a sun
+    // in it, join the lights in `World::sample_light`
+    fn is_sampled(&self) -> bool {
+        false
+    }
+
+    // a unit direction towards the environment and its solid angle density
+    fn sample(&self, _sampler: &mut dyn Sampler) -> Option<(Vec3, f64)> {
+        None
+    }
+
+    // density of `sample` returning `direction`
+    fn pdf(&self, _direction: &Vec3) -> f64 {
+        0.
+    }
+}
+
+// blends from `bottom` straight down to `top` straight up
+pub struct Gradient {
+    pub bottom: Color,
+    pub top: Color,
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let t = 0.5 * (direction.unit().y() + 1.);
+        (1. - t) * self.bottom + t * self.top
+    }
+}
+
+pub struct Constant {
+    pub color: Color,
+}
+
+impl Environment for Constant {
+    fn radiance(&self, _direction: &Vec3) -> Color {
+        self.color
+    }
+}
+
+/// Equirectangular image around the y axis, with -z in the middle. `rotation`
+/// turns it counterclockwise seen from above, in degrees.
+pub struct EnvironmentMap {
+    image: Image,
+    rotation: f64,
+    intensity: f64,
+    // pixels are picked by luminance, a row at a time then within it
+    row_cdf: Vec<f64>,
+    column_cdfs: Vec<Vec<f64>>,
+    // of the pixel weights, zero for a black image
+    total: f64,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Image, rotation: f64, intensity: f64) -> EnvironmentMap {
+        // rows near the poles cover less of the sphere
+        let weights: Vec<Vec<f64>> = (0..image.height)
+            .map(|y| {
+                let sin_theta = (PI * (y as f64 + 0.5) / image.height as f64).sin();
+                (0..image.width)
+                    .map(|x| image.pixel(x, y).luminance().max(0.) * sin_theta)
+                    .collect()
+            })
+            .collect();
+
+        let column_cdfs: Vec<Vec<f64>> = weights.iter().map(|row| cdf(row)).collect();
+        let row_sums: Vec<f64> = column_cdfs.iter().map(|cdf| cdf[cdf.len() - 1]).collect();
+        let row_cdf = cdf(&row_sums);
+        let total = row_cdf[row_cdf.len() - 1];
+
+        EnvironmentMap {
+            image,
+            rotation: rotation.to_radians(),
+            intensity,
+            row_cdf,
+            column_cdfs,
+            total,
+        }
+    }
+
+    // (u, v) in [0, 1)², v from the top row
+    fn uv(&self, direction: &Vec3) -> (f64, f64) {
+        let unit = direction.unit();
+        let phi = unit.x().atan2(-unit.z()) - self.rotation;
+        let u = (phi / (2. * PI) + 0.5).rem_euclid(1.);
+        let v = unit.y().clamp(-1., 1.).acos() / PI;
+        (u, v.min(1. - f64::EPSILON))
+    }
+
+    fn pixel_at(&self, (u, v): (f64, f64)) -> (usize, usize) {
+        let x = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let y = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+        (x, y)
+    }
+
+    // chance of `sample` picking pixel (x, y)
+    fn pixel_probability(&self, x: usize, y: usize) -> f64 {
+        let row = &self.column_cdfs[y];
+        let previous = match x {
+            0 => 0.,
+            _ => row[x - 1],
+        };
+        (row[x] - previous) / self.total
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let (x, y) = self.pixel_at(self.uv(direction));
+        self.intensity * self.image.pixel(x, y)
+    }
+
+    fn is_sampled(&self) -> bool {
+        self.total > 0.
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(Vec3, f64)> {
+        if self.total <= 0. {
+            return None;
+        }
+        // one pair picks the pixel, the other where within it
+        let (pick_row, pick_column) = sampler.get_2d();
+        let y = pick(&self.row_cdf, pick_row * self.total);
+        let row = &self.column_cdfs[y];
+        let x = pick(row, pick_column * row[row.len() - 1]);
+
+        let (offset_u, offset_v) = sampler.get_2d();
+        let u = (x as f64 + offset_u) / self.image.width as f64;
+        let v = (y as f64 + offset_v) / self.image.height as f64;
+        let phi = (u - 0.5) * 2. * PI + self.rotation;
+        let theta = v * PI;
+        let direction = Vec3(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+
+        let pdf = self.pdf(&direction);
+        match pdf > 0. {
+            true => Some((direction, pdf)),
+            false => None,
+        }
+    }
+
+    // the pixel's share of the weights spread over the solid angle it covers,
+    // du dv maps to 2π² sin(θ) dω
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        if self.total <= 0. {
+            return 0.;
+        }
+        let (u, v) = self.uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        let (x, y) = self.pixel_at((u, v));
+        let pixel_count = (self.image.width * self.image.height) as f64;
+
+        self.pixel_probability(x, y) * pixel_count / (2. * PI * PI * sin_theta)
+    }
+}
+
+// running sums of `weights`
+fn cdf(weights: &[f64]) -> Vec<f64> {
+    weights
+        .iter()
+        .scan(0., |sum, weight| {
+            *sum += weight;
+            Some(*sum)
+        })
+        .collect()
+}
+
+// first index whose running sum exceeds `target`, skipping zero weights
+fn pick(cdf: &[f64], target: f64) -> usize {
+    cdf.partition_point(|&sum| sum <= target).min(cdf.len() - 1)
+}
+
+/// Clear daylight sky with a sun, after Preetham et al. - "A Practical
+/// Analytic Model for Daylight", 1999. Angles are in degrees, the azimuth goes
+/// from -z towards +x like `EnvironmentMap`, turbidity is about 2 for very
+/// clear air and 10 for haze. Call `update` after changing them.
+#[derive(Clone)]
+pub struct Sky {
+    pub elevation: f64,
+    pub azimuth: f64,
+    pub turbidity: f64,
+
+    sun_direction: Vec3,
+    sun_radiance: Color,
+    // Perez coefficients A to E for luminance Y and chromaticities x and y
+    perez: [[f64; 5]; 3],
+    // Y, x, y at the zenith divided by the Perez function there
+    zenith: [f64; 3],
+}
+
+// angular radius of the sun disk in radians, a little larger than the real one
+const SUN_RADIUS: f64 = 0.005;
+// radiance of the sun before the atmosphere dims it, relative to a sky scaled
+// so that it's around 1 at midday
+const SUN_RADIANCE: f64 = 2e5;
+
+impl Sky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Sky {
+        let mut sky = Sky {
+            elevation,
+            azimuth,
+            turbidity,
+
+            sun_direction: Vec3(0., 1., 0.),
+            sun_radiance: Color(0., 0., 0.),
+            perez: [[0.; 5]; 3],
+            zenith: [0.; 3],
+        };
+        sky.update();
+
+        sky
+    }
+
+    pub fn update(&mut self) {
+        let t = self.turbidity;
+        let theta_sun = (90. - self.elevation.clamp(0., 90.)).to_radians();
+        let phi_sun = self.azimuth.to_radians();
+        self.sun_direction = Vec3(
+            theta_sun.sin() * phi_sun.sin(),
+            theta_sun.cos(),
+            -theta_sun.sin() * phi_sun.cos(),
+        );
+
+        self.perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        // zenith luminance in kcd/m², the tenth brings midday to around 1
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_sun);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192) / 10.;
+        let thetas = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.];
+        let polynomial = |coefficients: [[f64; 4]; 3]| {
+            let [t2, t1, t0] = coefficients.map(|row| {
+                (row.iter().zip(&thetas))
+                    .map(|(coefficient, power)| coefficient * power)
+                    .sum::<f64>()
+            });
+            t * t * t2 + t * t1 + t0
+        };
+        let zenith_x = polynomial([
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = polynomial([
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let zenith = [zenith_luminance.max(0.), zenith_x, zenith_y];
+        self.zenith =
+            [0, 1, 2].map(|channel| zenith[channel] / perez(&self.perez[channel], 1., theta_sun));
+
+        self.sun_radiance = SUN_RADIANCE * sun_transmittance(theta_sun, t);
+    }
+
+    // the sky alone, without the sun disk
+    fn sky_radiance(&self, unit: &Vec3) -> Color {
+        // Perez et al. only covers the upper hemisphere, below it the horizon repeats
+        let cos_theta = unit.y().max(0.01);
+        let gamma = unit.dot(&self.sun_direction).clamp(-1., 1.).acos();
+        let [luminance, x, y] = [0, 1, 2]
+            .map(|channel| self.zenith[channel] * perez(&self.perez[channel], cos_theta, gamma));
+
+        xyy_to_rgb(x, y, luminance)
+    }
+
+    fn cos_sun_radius(&self) -> f64 {
+        SUN_RADIUS.cos()
+    }
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let unit = direction.unit();
+        let sky = self.sky_radiance(&unit);
+        match unit.dot(&self.sun_direction) >= self.cos_sun_radius() {
+            true => sky + self.sun_radiance,
+            false => sky,
+        }
+    }
+
+    fn is_sampled(&self) -> bool {
+        true
+    }
+
+    // uniform over the sun disk, the rest of the sky is left to the bsdf
+    fn sample(&self, sampler: &mut dyn Sampler) -> Option<(Vec3, f64)> {
+        let (u, v) = sampler.get_2d();
+        let cos_theta_max = self.cos_sun_radius();
+        let cos_theta = 1. + u * (cos_theta_max - 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * v;
+
+        let w = self.sun_direction;
+        let (u, v) = w.orthonormal_basis();
+        let direction = sin_theta * (phi.cos() * u + phi.sin() * v) + cos_theta * w;
+        Some((direction, self.pdf(&direction)))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let cos_theta_max = self.cos_sun_radius();
+        match direction.unit().dot(&self.sun_direction) >= cos_theta_max {
+            true => 1. / (2. * PI * (1. - cos_theta_max)),
+            false => 0.,
+        }
+    }
+}
+
+// Perez et al. - "An All-Weather Model for Sky Luminance Distribution", 1993
+// `gamma` is the angle to the sun
+fn perez(coefficients: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = *coefficients;
+    (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+// CIE xyY to linear sRGB
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0. {
+        return Color(0., 0., 0.);
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1. - x - y) / y * luminance;
+    Color(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.),
+    )
+}
+
+// Rayleigh and aerosol extinction along the path sunlight takes through the
+// air, at a wavelength for each of red, green and blue
+// Preetham et al., appendix A.2, without ozone and water vapour
+fn sun_transmittance(theta_sun: f64, turbidity: f64) -> Color {
+    // relative optical mass, Kasten's fit keeps it finite at the horizon
+    let degrees = theta_sun.to_degrees();
+    let mass = 1. / (theta_sun.cos() + 0.15 * (93.885 - degrees).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    let [r, g, b] = [0.68, 0.55, 0.44].map(|micrometres: f64| {
+        let rayleigh = 0.008735 * micrometres.powf(-4.08);
+        let aerosol = beta * micrometres.powf(-1.3);
+        (-(rayleigh + aerosol) * mass).exp()
+    });
+    Color(r, g, b)
+}
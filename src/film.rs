@@ -0,0 +1,101 @@
+use egui::{Color32, ColorImage};
+
+use crate::tonemap::ToneMapping;
+use crate::vec3::Color;
+
+// luminance below which a pixel's error is measured against this instead,
+// so nearly black pixels don't chase a relative error they can't see
+const DARK_LUMINANCE: f64 = 0.05;
+
+/// Linear radiance accumulated over every sample pass.
+/// Stays in floating point so averaging never loses precision;
+/// conversion for display happens separately in `to_color_image`.
+#[derive(Clone)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    // row-major from the top
+    pub pixels: Vec<FilmPixel>,
+    // passes over the film, adaptive sampling skips converged pixels in later ones
+    pub passes: i32,
+}
+
+/// Everything one pixel has gathered, with enough statistics to tell how
+/// noisy its average still is.
+#[derive(Clone, Copy)]
+pub struct FilmPixel {
+    // running sum of radiance
+    pub sum: Color,
+    pub samples: i32,
+    // Welford's running mean of the luminance and sum of squared differences from it
+    mean_luminance: f64,
+    squared_deviations: f64,
+}
+
+impl FilmPixel {
+    const EMPTY: FilmPixel = FilmPixel {
+        sum: Color(0., 0., 0.),
+        samples: 0,
+        mean_luminance: 0.,
+        squared_deviations: 0.,
+    };
+
+    pub fn add(&mut self, radiance: Color) {
+        self.sum += radiance;
+        self.samples += 1;
+
+        let luminance = radiance.luminance();
+        let delta = luminance - self.mean_luminance;
+        self.mean_luminance += delta / self.samples as f64;
+        self.squared_deviations += delta * (luminance - self.mean_luminance);
+    }
+
+    pub fn average(&self) -> Color {
+        if self.samples == 0 {
+            return Color(0., 0., 0.);
+        }
+        self.sum / self.samples as f64
+    }
+
+    // standard error of the mean luminance relative to the mean itself,
+    // roughly how far off the pixel could still be
+    pub fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let samples = self.samples as f64;
+        let variance = self.squared_deviations / (samples - 1.);
+        (variance / samples).sqrt() / self.mean_luminance.max(DARK_LUMINANCE)
+    }
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        Film {
+            width,
+            height,
+            pixels: vec![FilmPixel::EMPTY; width * height],
+            passes: 0,
+        }
+    }
+
+    pub fn average(&self, pixel_idx: usize) -> Color {
+        self.pixels[pixel_idx].average()
+    }
+
+    // over the whole film, pixels that converged early pull it down
+    pub fn mean_samples(&self) -> f64 {
+        let total: i64 = self.pixels.iter().map(|pixel| pixel.samples as i64).sum();
+        total as f64 / self.pixels.len() as f64
+    }
+
+    pub fn to_color_image(&self, tone_mapping: &ToneMapping) -> ColorImage {
+        let mut image = ColorImage::new([self.width, self.height], Color32::BLACK);
+        for (pixel_idx, pixel) in image.pixels.iter_mut().enumerate() {
+            let [r, g, b] = tone_mapping.apply(self.average(pixel_idx));
+            *pixel = Color32::from_rgb(r, g, b);
+        }
+
+        image
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::vec3::Color;
+
+/// Decoded image in linear color, rows from the top.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn load(path: &Path) -> Result<Image, String> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let image = match extension.as_deref() {
+            Some("png") => load_png(path),
+            Some("ppm") => load_ppm(path),
+            Some("hdr") => load_hdr(path),
+            _ => Err("unsupported image format, expected .png, .ppm or .hdr".to_string()),
+        };
+
+        image.map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+// 8-bit images are sRGB encoded
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = value as f64 / 255.;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn load_png(path: &Path) -> Result<Image, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    // palettes, low bit depths and 16 bit channels all come out as 8 bit
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut data)
+        .map_err(|err| err.to_string())?;
+
+    let channels = info.color_type.samples();
+    let pixels = data[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| match channels {
+            1 | 2 => {
+                let grey = srgb_to_linear(pixel[0]);
+                Color(grey, grey, grey)
+            }
+            _ => Color(
+                srgb_to_linear(pixel[0]),
+                srgb_to_linear(pixel[1]),
+                srgb_to_linear(pixel[2]),
+            ),
+        })
+        .collect();
+
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+// both the plain (P3) and binary (P6) flavours
+fn load_ppm(path: &Path) -> Result<Image, String> {
+    let data = std::fs::read(path).map_err(|err| err.to_string())?;
+
+    // header: magic, width, height, max value, separated by whitespace and comments
+    let mut header = vec![];
+    let mut pos = 0;
+    while header.len() < 4 {
+        while pos < data.len() && data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos < data.len() && data[pos] == b'#' {
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err("truncated header".to_string());
+        }
+        header.push(String::from_utf8_lossy(&data[start..pos]).to_string());
+    }
+
+    let parse = |value: &str| {
+        value
+            .parse::<usize>()
+            .map_err(|_| format!("invalid header value \"{}\"", value))
+    };
+    let (width, height, max_value) = (parse(&header[1])?, parse(&header[2])?, parse(&header[3])?);
+    if max_value == 0 || max_value > 255 {
+        return Err(format!("unsupported max value {}", max_value));
+    }
+    let scale = |value: usize| srgb_to_linear((value * 255 / max_value) as u8);
+
+    let values: Vec<usize> = match header[0].as_str() {
+        "P3" => String::from_utf8_lossy(&data[pos..])
+            .split_whitespace()
+            .map(parse)
+            .collect::<Result<_, _>>()?,
+        // a single whitespace byte separates the header from the samples
+        "P6" => data[(pos + 1).min(data.len())..]
+            .iter()
+            .map(|&value| value as usize)
+            .collect(),
+        magic => return Err(format!("unsupported ppm type {}", magic)),
+    };
+    if values.len() < width * height * 3 {
+        return Err("fewer samples than the header promises".to_string());
+    }
+    if let Some(value) = values[..width * height * 3]
+        .iter()
+        .find(|&&value| value > max_value)
+    {
+        return Err(format!(
+            "sample {} above the max value {}",
+            value, max_value
+        ));
+    }
+
+    let pixels = values
+        .chunks_exact(3)
+        .take(width * height)
+        .map(|rgb| Color(scale(rgb[0]), scale(rgb[1]), scale(rgb[2])))
+        .collect();
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+// Radiance RGBE, flat or with run-length encoded scanlines, already linear
+// Ward - "Real Pixels", Graphics Gems II, 1991
+fn load_hdr(path: &Path) -> Result<Image, String> {
+    let data = std::fs::read(path).map_err(|err| err.to_string())?;
+
+    // header lines up to a blank one, then the resolution line
+    let mut lines: Vec<String> = vec![];
+    let mut pos = 0;
+    while lines.len() < 2 || !lines[lines.len() - 2].is_empty() {
+        let end = (data[pos..].iter().position(|&byte| byte == b'\n'))
+            .ok_or_else(|| "truncated header".to_string())?;
+        lines.push(String::from_utf8_lossy(&data[pos..pos + end]).to_string());
+        pos += end + 1;
+    }
+    if !lines[0].starts_with("#?") {
+        return Err("not a Radiance HDR file".to_string());
+    }
+    if let Some(format) = lines.iter().find_map(|line| line.strip_prefix("FORMAT=")) {
+        if format != "32-bit_rle_rgbe" {
+            return Err(format!("unsupported format {}", format));
+        }
+    }
+
+    let resolution = lines[lines.len() - 1]
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let (height, width) = match resolution.as_slice() {
+        ["-Y", height, "+X", width] => (height.parse::<usize>(), width.parse::<usize>()),
+        _ => return Err("only top to bottom, left to right images are supported".to_string()),
+    };
+    let (height, width) = match (height, width) {
+        (Ok(height), Ok(width)) if height > 0 && width > 0 => (height, width),
+        _ => return Err("invalid resolution".to_string()),
+    };
+
+    let truncated = || "fewer pixels than the header promises".to_string();
+    let mut rgbe = vec![0u8; width * height * 4];
+    for scanline in rgbe.chunks_exact_mut(width * 4) {
+        let encoded = data.get(pos..pos + 4).ok_or_else(truncated)?;
+        let is_rle = (8..0x8000).contains(&width)
+            && encoded[0] == 2
+            && encoded[1] == 2
+            && ((encoded[2] as usize) << 8 | encoded[3] as usize) == width;
+        if !is_rle {
+            let flat = data.get(pos..pos + width * 4).ok_or_else(truncated)?;
+            scanline.copy_from_slice(flat);
+            pos += width * 4;
+            continue;
+        }
+        pos += 4;
+
+        // each channel is stored separately, as runs and literal stretches
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = *data.get(pos).ok_or_else(truncated)? as usize;
+                let (count, is_run) = match count > 128 {
+                    true => (count - 128, true),
+                    false => (count, false),
+                };
+                if count == 0 || x + count > width {
+                    return Err("corrupt run length".to_string());
+                }
+                for offset in 0..count {
+                    let value_pos = match is_run {
+                        true => pos + 1,
+                        false => pos + 1 + offset,
+                    };
+                    scanline[(x + offset) * 4 + channel] =
+                        *data.get(value_pos).ok_or_else(truncated)?;
+                }
+                pos += match is_run {
+                    true => 2,
+                    false => 1 + count,
+                };
+                x += count;
+            }
+        }
+    }
+
+    let pixels = rgbe
+        .chunks_exact(4)
+        .map(|rgbe| match rgbe[3] {
+            0 => Color(0., 0., 0.),
+            exponent => {
+                let scale = 2f64.powi(exponent as i32 - (128 + 8));
+                Color(
+                    (rgbe[0] as f64 + 0.5) * scale,
+                    (rgbe[1] as f64 + 0.5) * scale,
+                    (rgbe[2] as f64 + 0.5) * scale,
+                )
+            }
+        })
+        .collect();
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
@@ -1,18 +1,83 @@
+mod aabb;
+mod bvh;
 mod camera;
+mod cli;
+mod csg;
+mod environment;
+mod film;
+mod image;
+mod medium;
+mod mesh;
+mod obj;
+mod output;
 mod ray;
+mod render;
+mod roots;
+mod sampler;
+mod scene;
+mod texture;
+mod tonemap;
 mod vec3;
 mod world;
 
 mod app;
 
+use std::path::Path;
+use std::process;
+
+use cli::Options;
+use scene::Scene;
+
 fn main() {
     // Log to stdout (if you run with `RUST_LOG=debug`).
     // tracing_subscriber::fmt::init();
 
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+
+    let mut scene = match &options.scene {
+        Some(path) => scene::load(path),
+        None => scene::parse(scene::DEFAULT_SCENE, "default scene", Path::new(".")),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+    if let Err(err) = options.apply(&mut scene) {
+        eprintln!("error: {}\n\n{}", err, cli::USAGE);
+        process::exit(2);
+    }
+
+    if options.headless {
+        run_headless(scene, &options);
+        return;
+    }
+
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
         "eframe template",
         native_options,
-        Box::new(|cc| Box::new(app::App::new(cc))),
+        Box::new(move |cc| Box::new(app::App::new(cc, scene, &options))),
     );
 }
+
+fn run_headless(scene: Scene, options: &Options) {
+    let film = render::render(&scene.world, &scene.camera, &scene.config);
+    if let Err(err) = output::write_image(&film, &scene.tone_mapping, &options.output) {
+        eprintln!(
+            "error: could not write {}: {}",
+            options.output.display(),
+            err
+        );
+        process::exit(1);
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::sampler::{hash, to_unit};
+use crate::vec3::Vec3;
+use crate::world::{FaceKind, HitRecord, Hittable, Material};
+
+// Participating media
+// Shirley - "Ray Tracing: The Next Week", 9
+
+/// Smoke or fog of uniform density filling `boundary`. Rays going through it
+/// scatter at an exponentially distributed distance, off `phase`, which is
+/// normally `Isotropic`.
+///
+/// The boundary only gets asked for its first two crossings, so it has to be
+/// convex: rays leaving a concave part and re-entering it go through the gap
+/// as if it were filled.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1. / density,
+            phase,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // the whole stretch of the line inside, the ray may start in the middle of it
+        let enter = self.boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(ray, enter.t + 0.0001, f64::INFINITY)?;
+
+        let t_enter = enter.t.max(t_min).max(0.);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction.length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * (1. - ray_random(ray)).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        Some(HitRecord {
+            t,
+            hit_point: ray.at(t),
+            // a point in the volume has no surface, isotropic scattering ignores these
+            normal: Vec3(1., 0., 0.),
+            face: FaceKind::Front,
+            uv: (0., 0.),
+            material: self.phase.clone(),
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+// `hit` has no sampler to draw from, so the ray itself is hashed: it already
+// came out of the sampler, and the same ray always scatters at the same spot
+fn ray_random(ray: &Ray) -> f64 {
+    let Ray {
+        origin,
+        direction,
+        time,
+    } = ray;
+    to_unit(hash(&[
+        origin.0.to_bits(),
+        origin.1.to_bits(),
+        origin.2.to_bits(),
+        direction.0.to_bits(),
+        direction.1.to_bits(),
+        direction.2.to_bits(),
+        time.to_bits(),
+    ]))
+}
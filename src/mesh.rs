@@ -0,0 +1,497 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhTree;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::{Point3, Vec3};
+use crate::world::{
+    area_pdf_to_solid_angle, intervals_from_crossings, HitRecord, Hittable, Interval, Material,
+    SurfaceSample,
+};
+
+// Watertight ray/triangle intersection
+// Woop, Benthin, Wald - "Watertight Ray/Triangle Intersection", JCGT 2013
+// https://jcgt.org/published/0002/01/05/
+
+// per-ray setup, shared by every triangle the ray is tested against
+struct RayShear {
+    kx: usize,
+    ky: usize,
+    kz: usize,
+    sx: f64,
+    sy: f64,
+    sz: f64,
+}
+
+impl RayShear {
+    fn new(ray: &Ray) -> RayShear {
+        let d = ray.direction;
+        // permute so the dominant axis becomes z, keeping the winding
+        let kz = if d.0.abs() > d.1.abs() {
+            if d.0.abs() > d.2.abs() {
+                0
+            } else {
+                2
+            }
+        } else if d.1.abs() > d.2.abs() {
+            1
+        } else {
+            2
+        };
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        if d[kz] < 0. {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        RayShear {
+            kx,
+            ky,
+            kz,
+            sx: d[kx] / d[kz],
+            sy: d[ky] / d[kz],
+            sz: 1. / d[kz],
+        }
+    }
+}
+
+// returns t and the barycentric weights of v0, v1, v2
+fn intersect_triangle(
+    ray: &Ray,
+    shear: &RayShear,
+    vertices: [Point3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, [f64; 3])> {
+    let RayShear {
+        kx,
+        ky,
+        kz,
+        sx,
+        sy,
+        sz,
+    } = *shear;
+
+    let a = vertices[0] - ray.origin;
+    let b = vertices[1] - ray.origin;
+    let c = vertices[2] - ray.origin;
+
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    // scaled barycentrics, an edge shared by two triangles gives exactly
+    // the same value with opposite sign in both so no ray slips through
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0. || v < 0. || w < 0.) && (u > 0. || v > 0. || w > 0.) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0. {
+        return None;
+    }
+
+    let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, [u / det, v / det, w / det]))
+}
+
+// counter-clockwise winding faces outwards
+fn geometric_normal(vertices: [Point3; 3]) -> Vec3 {
+    (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .unit()
+}
+
+fn area(vertices: [Point3; 3]) -> f64 {
+    (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .length()
+        / 2.
+}
+
+// uniformly distributed over the triangle's area
+fn sample_point(vertices: [Point3; 3], (u, t): (f64, f64)) -> Point3 {
+    let s = u.sqrt();
+
+    (1. - s) * vertices[0] + (s * (1. - t)) * vertices[1] + (s * t) * vertices[2]
+}
+
+// Triangle
+
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    pub material: Arc<dyn Material>,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let shear = RayShear::new(ray);
+        let (t, _) = intersect_triangle(ray, &shear, self.vertices, t_min, t_max)?;
+
+        Some(HitRecord::new(
+            ray,
+            t,
+            ray.at(t),
+            geometric_normal(self.vertices),
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.vertices[0], self.vertices[1]).include(self.vertices[2])
+    }
+
+    fn sample_surface(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+        let point = sample_point(self.vertices, sampler.get_2d());
+        let pdf = 1. / area(self.vertices);
+        Some(SurfaceSample {
+            point,
+            pdf: area_pdf_to_solid_angle(pdf, origin, &point, &geometric_normal(self.vertices)),
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+            time: 0.,
+        };
+        let shear = RayShear::new(&ray);
+        match intersect_triangle(&ray, &shear, self.vertices, 0.001, f64::INFINITY) {
+            Some((t, _)) => area_pdf_to_solid_angle(
+                1. / area(self.vertices),
+                origin,
+                &ray.at(t),
+                &geometric_normal(self.vertices),
+            ),
+            None => 0.,
+        }
+    }
+}
+
+// TriangleMesh
+
+/// Indexed triangles over shared vertex buffers, with an internal BVH.
+/// `normals` and `uvs` are either empty or hold one entry per position.
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    triangles: Vec<[u32; 3]>,
+    material: Arc<dyn Material>,
+    tree: BvhTree,
+    // running total of triangle areas, for picking triangles by area
+    area_cdf: Vec<f64>,
+    // every edge shared by exactly two triangles, so the mesh bounds a volume
+    closed: bool,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        triangles: Vec<[u32; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Result<TriangleMesh, String> {
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(format!(
+                "{} normals for {} positions",
+                normals.len(),
+                positions.len()
+            ));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(format!(
+                "{} uvs for {} positions",
+                uvs.len(),
+                positions.len()
+            ));
+        }
+        if let Some(index) = triangles
+            .iter()
+            .flatten()
+            .find(|&&index| index as usize >= positions.len())
+        {
+            return Err(format!(
+                "vertex index {} out of range for {} positions",
+                index,
+                positions.len()
+            ));
+        }
+
+        let mut mesh = TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            triangles,
+            material,
+            tree: BvhTree::build(&[]),
+            area_cdf: vec![],
+            closed: false,
+        };
+        let bounds: Vec<Aabb> = (0..mesh.triangles.len())
+            .map(|i| {
+                let [v0, v1, v2] = mesh.vertices(i);
+                Aabb::new(v0, v1).include(v2)
+            })
+            .collect();
+        mesh.tree = BvhTree::build(&bounds);
+        mesh.area_cdf = (0..mesh.triangles.len())
+            .scan(0., |total, i| {
+                *total += area(mesh.vertices(i));
+                Some(*total)
+            })
+            .collect();
+        mesh.closed = mesh.is_closed();
+
+        Ok(mesh)
+    }
+
+    fn is_closed(&self) -> bool {
+        if self.triangles.is_empty() {
+            return false;
+        }
+        // importers split vertices along seams, so compare positions rather than indices
+        let mut welded: HashMap<[u64; 3], usize> = HashMap::new();
+        let ids: Vec<usize> = self
+            .positions
+            .iter()
+            .map(|p| {
+                let next_id = welded.len();
+                *welded
+                    .entry([p.0.to_bits(), p.1.to_bits(), p.2.to_bits()])
+                    .or_insert(next_id)
+            })
+            .collect();
+
+        let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+        for triangle in &self.triangles {
+            for k in 0..3 {
+                let a = ids[triangle[k] as usize];
+                let b = ids[triangle[(k + 1) % 3] as usize];
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        edges.values().all(|&count| count == 2)
+    }
+
+    fn total_area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.)
+    }
+
+    fn vertices(&self, triangle: usize) -> [Point3; 3] {
+        self.triangles[triangle].map(|index| self.positions[index as usize])
+    }
+
+    fn hit_triangle(
+        &self,
+        triangle: usize,
+        ray: &Ray,
+        shear: &RayShear,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let vertices = self.vertices(triangle);
+        let (t, weights) = intersect_triangle(ray, shear, vertices, t_min, t_max)?;
+        let indices = self.triangles[triangle].map(|index| index as usize);
+
+        let mut rec = HitRecord::new(
+            ray,
+            t,
+            ray.at(t),
+            geometric_normal(vertices),
+            self.material.clone(),
+        );
+
+        // smooth shading, kept on the same side as the geometric normal
+        if !self.normals.is_empty() {
+            let shading_normal = (0..3)
+                .map(|k| weights[k] * self.normals[indices[k]])
+                .sum::<Vec3>();
+            if !shading_normal.near_zero() {
+                let shading_normal = shading_normal.unit();
+                rec.normal = match shading_normal.dot(&rec.normal) < 0. {
+                    true => -1. * shading_normal,
+                    false => shading_normal,
+                };
+            }
+        }
+        if !self.uvs.is_empty() {
+            rec.uv = (0..3).fold((0., 0.), |(u, v), k| {
+                (
+                    u + weights[k] * self.uvs[indices[k]].0,
+                    v + weights[k] * self.uvs[indices[k]].1,
+                )
+            });
+        }
+
+        Some(rec)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let shear = RayShear::new(ray);
+        self.tree
+            .hit(ray, t_min, t_max, |triangle, t_max| {
+                self.hit_triangle(triangle, ray, &shear, t_min, t_max)
+            })
+            .map(|(_, rec)| rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounding_box()
+    }
+
+    fn sample_surface(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+        let total_area = self.total_area();
+        if total_area <= 0. {
+            return None;
+        }
+
+        let target = sampler.get_1d() * total_area;
+        let triangle = self
+            .area_cdf
+            .partition_point(|&area| area <= target)
+            .min(self.triangles.len() - 1);
+        let vertices = self.vertices(triangle);
+        let point = sample_point(vertices, sampler.get_2d());
+
+        Some(SurfaceSample {
+            point,
+            pdf: self.surface_pdf(origin, &(point - *origin)),
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let total_area = self.total_area();
+        if total_area <= 0. {
+            return 0.;
+        }
+
+        // a direction can cross the mesh several times, each crossing could
+        // have been sampled, so walk them front to back
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+            time: 0.,
+        };
+        let shear = RayShear::new(&ray);
+        let mut pdf = 0.;
+        let mut t_min = 0.001;
+
+        while let Some((triangle, t)) =
+            self.tree
+                .hit(&ray, t_min, f64::INFINITY, |triangle, t_max| {
+                    let (t, _) =
+                        intersect_triangle(&ray, &shear, self.vertices(triangle), t_min, t_max)?;
+                    Some(t)
+                })
+        {
+            let vertices = self.vertices(triangle);
+            pdf += area_pdf_to_solid_angle(
+                1. / total_area,
+                origin,
+                &ray.at(t),
+                &geometric_normal(vertices),
+            );
+            t_min = t * (1. + 1e-9) + 1e-9;
+        }
+
+        pdf
+    }
+
+    fn is_solid(&self) -> bool {
+        self.closed
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        // the traversal never narrows, so it visits every crossing
+        let shear = RayShear::new(ray);
+        let mut crossings = vec![];
+        self.tree.hit(
+            ray,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            |triangle, _| -> Option<f64> {
+                crossings.extend(self.hit_triangle(
+                    triangle,
+                    ray,
+                    &shear,
+                    f64::NEG_INFINITY,
+                    f64::INFINITY,
+                ));
+                None
+            },
+        );
+
+        intervals_from_crossings(crossings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::SolidColor;
+    use crate::world::Lambertian;
+
+    // the cube [-1, 1]³, vertex i at (±1, ±1, ±1) with bits x, y, z of i picking the signs
+    fn cube() -> TriangleMesh {
+        let positions = (0..8)
+            .map(|i| {
+                let sign = |bit: u32| if i >> bit & 1 == 1 { 1. } else { -1. };
+                Vec3(sign(0), sign(1), sign(2))
+            })
+            .collect();
+        let triangles = vec![
+            // +z, split along x = y
+            [4, 5, 7],
+            [4, 7, 6],
+            // -z, split along x = -y
+            [0, 2, 1],
+            [2, 3, 1],
+            [1, 3, 7],
+            [1, 7, 5],
+            [0, 4, 6],
+            [0, 6, 2],
+            [2, 6, 7],
+            [2, 7, 3],
+            [0, 1, 5],
+            [0, 5, 4],
+        ];
+        let material = Arc::new(Lambertian {
+            albedo: Arc::new(SolidColor {
+                color: Vec3(0.5, 0.5, 0.5),
+            }),
+        });
+        TriangleMesh::new(positions, vec![], vec![], triangles, material).unwrap()
+    }
+
+    #[test]
+    fn shared_edge_gives_one_interval() {
+        let mesh = cube();
+        assert!(mesh.is_solid());
+
+        // enters through the edge between the two +z triangles, leaves through
+        // the middle of a single -z triangle
+        let ray = Ray {
+            origin: Vec3(0.3, 0.3, 5.),
+            direction: Vec3(0., 0., -1.),
+            time: 0.,
+        };
+        let intervals = mesh.intervals(&ray);
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].enter.t - 4.).abs() < 1e-9);
+        assert!((intervals[0].exit.t - 6.).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::mesh::TriangleMesh;
+use crate::texture::{ImageTexture, SolidColor, Texture};
+use crate::vec3::{Color, Point3, Vec3};
+use crate::world::{Dielectric, Lambertian, Material, Metal};
+
+// Wavefront OBJ + MTL importer
+// http://paulbourke.net/dataformats/obj/
+// http://paulbourke.net/dataformats/mtl/
+
+// one face corner, as indices into the position / texcoord / normal lists
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct Face {
+    corners: [Corner; 3],
+    // 0 means smoothing is off
+    smoothing_group: u32,
+    group: usize,
+}
+
+// where a mesh vertex gets its normal from
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum NormalSource {
+    None,
+    Given(usize),
+    Smooth(u32),
+    Flat(usize),
+}
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    faces: Vec<Face>,
+    // (group name, material name) pairs in order of appearance
+    groups: Vec<(String, String)>,
+    material_libraries: Vec<String>,
+}
+
+/// Loads every group / material combination of an .obj file as its own mesh.
+/// Materials come from the referenced .mtl files, anything they can't express
+/// becomes `fallback`. A given `material_override` replaces them all.
+pub fn load(
+    path: &Path,
+    fallback: Arc<dyn Material>,
+    material_override: Option<Arc<dyn Material>>,
+) -> Result<Vec<TriangleMesh>, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let data = parse_obj(&source).map_err(|err| format!("{}:{}", path.display(), err))?;
+
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    if material_override.is_none() {
+        let directory = path.parent().unwrap_or_else(|| Path::new("."));
+        for library in &data.material_libraries {
+            let library_path = directory.join(library);
+            let source = fs::read_to_string(&library_path)
+                .map_err(|err| format!("{}: {}", library_path.display(), err))?;
+            let descs =
+                parse_mtl(&source).map_err(|err| format!("{}:{}", library_path.display(), err))?;
+
+            for (name, desc) in descs {
+                let material = desc.build(directory)?.unwrap_or_else(|| {
+                    eprintln!(
+                        "warning: {}: illum {} of material \"{}\" is not supported, using the fallback",
+                        library_path.display(),
+                        desc.illum,
+                        name
+                    );
+                    fallback.clone()
+                });
+                materials.insert(name, material);
+            }
+        }
+    }
+
+    let smooth_normals = smooth_normals(&data);
+
+    let mut meshes = vec![];
+    for (group, (group_name, material_name)) in data.groups.iter().enumerate() {
+        let faces: Vec<&Face> = data.faces.iter().filter(|f| f.group == group).collect();
+        if faces.is_empty() {
+            continue;
+        }
+
+        let material = match &material_override {
+            Some(material) => material.clone(),
+            None => materials.get(material_name).cloned().unwrap_or_else(|| {
+                if !material_name.is_empty() {
+                    eprintln!(
+                        "warning: {}: material \"{}\" not found, using the fallback",
+                        path.display(),
+                        material_name
+                    );
+                }
+                fallback.clone()
+            }),
+        };
+
+        let mesh = build_mesh(&data, &faces, &smooth_normals, material)
+            .map_err(|err| format!("{}: group \"{}\": {}", path.display(), group_name, err))?;
+        meshes.push(mesh);
+    }
+
+    Ok(meshes)
+}
+
+fn build_mesh(
+    data: &ObjData,
+    faces: &[&Face],
+    smooth_normals: &HashMap<(usize, u32), Vec3>,
+    material: Arc<dyn Material>,
+) -> Result<TriangleMesh, String> {
+    let has_uvs = faces
+        .iter()
+        .all(|f| f.corners.iter().all(|c| c.uv.is_some()));
+    let has_normals = faces
+        .iter()
+        .any(|f| f.smoothing_group != 0 || f.corners.iter().any(|c| c.normal.is_some()));
+
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut triangles = vec![];
+    // obj indexes positions, uvs and normals separately, meshes share one index
+    let mut vertices: HashMap<(usize, Option<usize>, NormalSource), u32> = HashMap::new();
+
+    for (face_idx, face) in faces.iter().enumerate() {
+        let mut triangle = [0; 3];
+        for (k, corner) in face.corners.iter().enumerate() {
+            let uv = corner.uv.filter(|_| has_uvs);
+            let normal = match (has_normals, corner.normal) {
+                (false, _) => NormalSource::None,
+                (true, Some(normal)) => NormalSource::Given(normal),
+                (true, None) if face.smoothing_group != 0 => {
+                    NormalSource::Smooth(face.smoothing_group)
+                }
+                (true, None) => NormalSource::Flat(face_idx),
+            };
+
+            let next_index = positions.len() as u32;
+            triangle[k] = *vertices
+                .entry((corner.position, uv, normal))
+                .or_insert_with(|| {
+                    positions.push(data.positions[corner.position]);
+                    if let Some(uv) = uv {
+                        uvs.push(data.uvs[uv]);
+                    }
+                    match normal {
+                        NormalSource::None => (),
+                        NormalSource::Given(normal) => normals.push(data.normals[normal]),
+                        NormalSource::Smooth(group) => {
+                            normals.push(smooth_normals[&(corner.position, group)])
+                        }
+                        NormalSource::Flat(_) => normals.push(face_normal(data, face)),
+                    }
+                    next_index
+                });
+        }
+        triangles.push(triangle);
+    }
+
+    TriangleMesh::new(positions, normals, uvs, triangles, material)
+}
+
+// area weighted, unnormalized
+fn face_normal(data: &ObjData, face: &Face) -> Vec3 {
+    let [a, b, c] = face.corners.map(|corner| data.positions[corner.position]);
+    (b - a).cross(&(c - a))
+}
+
+// averages the normals of every face around a position within a smoothing group
+fn smooth_normals(data: &ObjData) -> HashMap<(usize, u32), Vec3> {
+    let mut normals: HashMap<(usize, u32), Vec3> = HashMap::new();
+    for face in data.faces.iter().filter(|f| f.smoothing_group != 0) {
+        let normal = face_normal(data, face);
+        for corner in &face.corners {
+            *normals
+                .entry((corner.position, face.smoothing_group))
+                .or_insert(Vec3(0., 0., 0.)) += normal;
+        }
+    }
+
+    for normal in normals.values_mut() {
+        if !normal.near_zero() {
+            *normal = normal.unit();
+        }
+    }
+
+    normals
+}
+
+// Parsing
+
+fn parse_obj(source: &str) -> Result<ObjData, String> {
+    let mut data = ObjData::default();
+    let mut group_name = String::new();
+    let mut material_name = String::new();
+    let mut group = None;
+    let mut smoothing_group = 0;
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let err = |message: String| format!("{}: {}", line_idx + 1, message);
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            None => continue,
+            Some(keyword) if keyword.starts_with('#') => continue,
+            Some(keyword) => keyword,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let xyz = parse_floats(&args, 3, 3).map_err(err)?;
+                data.positions.push(Point3(xyz[0], xyz[1], xyz[2]));
+            }
+            "vn" => {
+                let xyz = parse_floats(&args, 3, 3).map_err(err)?;
+                data.normals.push(Vec3(xyz[0], xyz[1], xyz[2]));
+            }
+            "vt" => {
+                let uv = parse_floats(&args, 1, 2).map_err(err)?;
+                data.uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.)));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(err("a face needs at least 3 vertices".to_string()));
+                }
+                let corners = args
+                    .iter()
+                    .map(|arg| parse_corner(arg, &data))
+                    .collect::<Result<Vec<Corner>, String>>()
+                    .map_err(err)?;
+
+                let group = *group.get_or_insert_with(|| {
+                    data.groups
+                        .push((group_name.clone(), material_name.clone()));
+                    data.groups.len() - 1
+                });
+                // polygons are triangulated as a fan around their first corner
+                for k in 1..corners.len() - 1 {
+                    data.faces.push(Face {
+                        corners: [corners[0], corners[k], corners[k + 1]],
+                        smoothing_group,
+                        group,
+                    });
+                }
+            }
+            "g" | "o" => {
+                group_name = args.join(" ");
+                group = None;
+            }
+            "usemtl" => {
+                material_name = args.join(" ");
+                group = None;
+            }
+            "s" => {
+                smoothing_group = match args.first() {
+                    None | Some(&"off") => 0,
+                    Some(value) => value
+                        .parse()
+                        .map_err(|_| err(format!("invalid smoothing group \"{}\"", value)))?,
+                };
+            }
+            "mtllib" => data
+                .material_libraries
+                .extend(args.iter().map(|arg| arg.to_string())),
+            // lines, points, curves and the like aren't renderable
+            _ => (),
+        }
+    }
+
+    Ok(data)
+}
+
+fn parse_corner(arg: &str, data: &ObjData) -> Result<Corner, String> {
+    let mut parts = arg.split('/');
+    let position = parse_index(parts.next(), data.positions.len(), "position")?
+        .ok_or_else(|| format!("face vertex \"{}\" has no position", arg))?;
+    let uv = parse_index(parts.next(), data.uvs.len(), "texture coordinate")?;
+    let normal = parse_index(parts.next(), data.normals.len(), "normal")?;
+
+    Ok(Corner {
+        position,
+        uv,
+        normal,
+    })
+}
+
+// 1-based, negative values count back from the end of the list
+fn parse_index(part: Option<&str>, len: usize, kind: &str) -> Result<Option<usize>, String> {
+    let part = match part {
+        None | Some("") => return Ok(None),
+        Some(part) => part,
+    };
+    let index: i64 = part
+        .parse()
+        .map_err(|_| format!("invalid {} index \"{}\"", kind, part))?;
+
+    let resolved = match index {
+        0 => None,
+        i if i > 0 => Some(i as usize - 1),
+        i => (len as i64 + i).try_into().ok(),
+    };
+    match resolved {
+        Some(i) if i < len => Ok(Some(i)),
+        _ => Err(format!("{} index {} out of range", kind, index)),
+    }
+}
+
+// reads at least `min` and at most `max` leading numbers, ignoring the rest
+fn parse_floats(args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if args.len() < min {
+        return Err(format!("expected {} numbers, got {}", min, args.len()));
+    }
+    args.iter()
+        .take(max)
+        .map(|arg| {
+            arg.parse()
+                .map_err(|_| format!("invalid number \"{}\"", arg))
+        })
+        .collect()
+}
+
+// Materials
+
+// the subset of an mtl entry we can map onto our materials
+struct MtlDesc {
+    diffuse: Color,
+    // replaces `diffuse`, relative to the .mtl file
+    diffuse_map: Option<String>,
+    specular: Color,
+    shininess: f64,
+    ior: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl Default for MtlDesc {
+    fn default() -> Self {
+        MtlDesc {
+            diffuse: Color(0.8, 0.8, 0.8),
+            diffuse_map: None,
+            specular: Color(0., 0., 0.),
+            shininess: 0.,
+            ior: 1.5,
+            dissolve: 1.,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlDesc {
+    // None when the illumination model has no counterpart here
+    fn build(&self, directory: &Path) -> Result<Option<Arc<dyn Material>>, String> {
+        let material: Arc<dyn Material> = match self.illum {
+            _ if self.dissolve < 1. => Arc::new(Dielectric { ior: self.ior }),
+            0..=2 => {
+                let albedo: Arc<dyn Texture> = match &self.diffuse_map {
+                    Some(map) => Arc::new(ImageTexture {
+                        image: Image::load(&directory.join(map))?,
+                    }),
+                    None => Arc::new(SolidColor {
+                        color: self.diffuse,
+                    }),
+                };
+                Arc::new(Lambertian { albedo })
+            }
+            3 | 5 | 8 => Arc::new(Metal {
+                albedo: Arc::new(SolidColor {
+                    color: self.specular,
+                }),
+                // Phong exponent to roughness, sqrt(2 / (Ns + 2))
+                fuzz: (2. / (self.shininess.max(0.) + 2.)).sqrt().min(1.),
+            }),
+            4 | 6 | 7 | 9 => Arc::new(Dielectric { ior: self.ior }),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(material))
+    }
+}
+
+fn parse_mtl(source: &str) -> Result<Vec<(String, MtlDesc)>, String> {
+    let mut descs: Vec<(String, MtlDesc)> = vec![];
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let err = |message: String| format!("{}: {}", line_idx + 1, message);
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            None => continue,
+            Some(keyword) if keyword.starts_with('#') => continue,
+            Some(keyword) => keyword,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            descs.push((args.join(" "), MtlDesc::default()));
+            continue;
+        }
+        let desc = match descs.last_mut() {
+            Some((_, desc)) => desc,
+            None => return Err(err(format!("\"{}\" before any newmtl", keyword))),
+        };
+
+        match keyword {
+            "Kd" | "Ks" => {
+                let rgb = parse_floats(&args, 1, 3).map_err(err)?;
+                // a single value means grey
+                let color = match rgb[..] {
+                    [r, g, b] => Color(r, g, b),
+                    _ => Color(rgb[0], rgb[0], rgb[0]),
+                };
+                match keyword {
+                    "Kd" => desc.diffuse = color,
+                    _ => desc.specular = color,
+                }
+            }
+            // options such as -s or -bm come before the file name
+            "map_Kd" => match args.last() {
+                Some(file) => desc.diffuse_map = Some(file.to_string()),
+                None => return Err(err("map_Kd without a file".to_string())),
+            },
+            "Ns" => desc.shininess = parse_floats(&args, 1, 1).map_err(err)?[0],
+            "Ni" => desc.ior = parse_floats(&args, 1, 1).map_err(err)?[0],
+            "d" => desc.dissolve = parse_floats(&args, 1, 1).map_err(err)?[0],
+            "Tr" => desc.dissolve = 1. - parse_floats(&args, 1, 1).map_err(err)?[0],
+            "illum" => {
+                desc.illum = args
+                    .first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| err("invalid illum".to_string()))?
+            }
+            // other texture maps, ambient, emission and vendor extensions
+            _ => (),
+        }
+    }
+
+    Ok(descs)
+}
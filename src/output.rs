@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::film::Film;
+use crate::tonemap::ToneMapping;
+use crate::vec3::Color;
+
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Pfm,
+    Hdr,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "pfm" => Some(ImageFormat::Pfm),
+            "hdr" => Some(ImageFormat::Hdr),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the film in the format picked by the file extension. PPM and PNG are
+/// display-ready 8-bit through `tone_mapping`, PFM and HDR keep the unclamped
+/// linear radiance.
+pub fn write_image(film: &Film, tone_mapping: &ToneMapping, path: &Path) -> io::Result<()> {
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unsupported extension, expected .ppm, .png, .pfm or .hdr",
+        )
+    })?;
+    let mut out = BufWriter::new(File::create(path)?);
+
+    match format {
+        ImageFormat::Ppm => write_ppm(film, tone_mapping, &mut out)?,
+        ImageFormat::Png => write_png(film, tone_mapping, &mut out)?,
+        ImageFormat::Pfm => write_pfm(film, &mut out)?,
+        ImageFormat::Hdr => write_hdr(film, &mut out)?,
+    }
+
+    out.flush()
+}
+
+// plain-text PPM (P3), tone mapped the same way as the viewer
+fn write_ppm(film: &Film, tone_mapping: &ToneMapping, out: &mut impl Write) -> io::Result<()> {
+    let image = film.to_color_image(tone_mapping);
+
+    writeln!(out, "P3\n{} {}\n255", film.width, film.height)?;
+    for pixel in &image.pixels {
+        writeln!(out, "{} {} {}", pixel.r(), pixel.g(), pixel.b())?;
+    }
+
+    Ok(())
+}
+
+fn write_png(film: &Film, tone_mapping: &ToneMapping, out: &mut impl Write) -> io::Result<()> {
+    let image = film.to_color_image(tone_mapping);
+    let data: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| [pixel.r(), pixel.g(), pixel.b()])
+        .collect();
+
+    let mut encoder = png::Encoder::new(out, film.width as u32, film.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(&data).map_err(png_error)
+}
+
+fn png_error(err: png::EncodingError) -> io::Error {
+    match err {
+        png::EncodingError::IoError(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+// Portable Float Map: little endian f32 rgb, rows stored bottom to top
+// http://www.pauldebevec.com/Research/HDR/PFM/
+fn write_pfm(film: &Film, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "PF\n{} {}\n-1.0", film.width, film.height)?;
+
+    for row in (0..film.height).rev() {
+        for col in 0..film.width {
+            let color = film.average(row * film.width + col);
+            for channel in [color.x(), color.y(), color.z()] {
+                out.write_all(&(channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Radiance RGBE with flat (uncompressed) scanlines
+// https://www.graphics.cornell.edu/~bjw/rgbe.html
+fn write_hdr(film: &Film, out: &mut impl Write) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        film.height, film.width
+    )?;
+
+    for pixel_idx in 0..film.width * film.height {
+        out.write_all(&to_rgbe(film.average(pixel_idx)))?;
+    }
+
+    Ok(())
+}
+
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let (r, g, b) = (color.x().max(0.), color.y().max(0.), color.z().max(0.));
+    let brightest = r.max(g).max(b);
+    if brightest < 1e-32 || !brightest.is_finite() {
+        return [0, 0, 0, 0];
+    }
+
+    // brightest = mantissa * 2^exponent, with mantissa in [0.5, 1)
+    let exponent = brightest.log2().floor() as i32 + 1;
+    let scale = 256. / 2f64.powi(exponent);
+
+    [
+        (r * scale).min(255.) as u8,
+        (g * scale).min(255.) as u8,
+        (b * scale).min(255.) as u8,
+        (exponent + 128) as u8,
+    ]
+}
@@ -3,10 +3,12 @@ use crate::vec3::{Point3, Vec3};
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    // instant within the shutter interval the ray samples, moving objects depend on it
+    pub time: f64,
 }
 
 impl Ray {
     pub fn at(&self, t: f64) -> Vec3 {
-        self.origin + self.direction.unit() * t
+        self.origin + self.direction * t
     }
 }
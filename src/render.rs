@@ -0,0 +1,332 @@
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::ray::Ray;
+use crate::sampler::{Sampler, SamplerKind};
+use crate::vec3::Color;
+use crate::world::{HitRecord, World};
+
+#[derive(Clone)]
+pub struct Config {
+    pub max_depth: usize,
+    // the most samples any one pixel gets
+    pub max_samples: i32,
+    // every pixel gets these before its noise estimate is trusted
+    pub min_samples: i32,
+    // relative error below which a pixel is left alone,
+    // None keeps sampling every pixel up to `max_samples`
+    pub noise_threshold: Option<f64>,
+    // stops the render after the pass that runs past it; on its own, each
+    // pass goes to the pixels noisier than average
+    pub time_budget: Option<Duration>,
+    pub width: usize,
+    pub height: usize,
+    pub sampler: SamplerKind,
+    // the same seed renders the same image, however many threads share the work
+    pub seed: u64,
+}
+
+impl Config {
+    // whether pixels can stop before `max_samples`, which takes `min_samples` first
+    pub fn is_adaptive(&self) -> bool {
+        self.noise_threshold.is_some() || self.time_budget.is_some()
+    }
+}
+
+// a finished sample pass, published to the ui
+pub struct Pass {
+    pub film: Film,
+}
+
+// Renderer
+
+/// Background worker that owns the scene and the film it accumulates into.
+/// Dropping it cancels the current pass and joins the thread.
+pub struct Renderer {
+    passes: Receiver<Pass>,
+    cancelled: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Renderer {
+    pub fn spawn(
+        world: Arc<World>,
+        camera: Camera,
+        config: Config,
+        ctx: egui::Context,
+    ) -> Renderer {
+        let (sender, passes) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let worker_cancelled = cancelled.clone();
+        let handle = thread::spawn(move || {
+            render_loop(&world, &camera, &config, &worker_cancelled, &sender, &ctx)
+        });
+
+        Renderer {
+            passes,
+            cancelled,
+            handle: Some(handle),
+        }
+    }
+
+    /// Most recent pass the worker has published since the last call, if any.
+    pub fn latest_pass(&self) -> Option<Pass> {
+        self.passes.try_iter().last()
+    }
+
+    /// Cancels the current pass and waits for the worker to let go of the world.
+    pub fn stop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Renders every pass on the calling thread, for use without a window.
+pub fn render(world: &World, camera: &Camera, config: &Config) -> Film {
+    let mut film = Film::new(config.width, config.height);
+    let never_cancelled = AtomicBool::new(false);
+    let start = Instant::now();
+
+    while let Some(active) = next_pass(&film, config, start) {
+        render_pass(world, camera, config, &mut film, &active, &never_cancelled);
+        let active_count = active.iter().filter(|&&active| active).count();
+        eprint!(
+            "\rpass {}: {} pixels sampled, {:.1} samples per pixel    ",
+            film.passes,
+            active_count,
+            film.mean_samples()
+        );
+    }
+    eprintln!();
+
+    film
+}
+
+fn render_loop(
+    world: &World,
+    camera: &Camera,
+    config: &Config,
+    cancelled: &AtomicBool,
+    sender: &Sender<Pass>,
+    ctx: &egui::Context,
+) {
+    let mut film = Film::new(config.width, config.height);
+    let start = Instant::now();
+
+    while let Some(active) = next_pass(&film, config, start) {
+        if !render_pass(world, camera, config, &mut film, &active, cancelled) {
+            return;
+        }
+
+        let pass = Pass { film: film.clone() };
+        if sender.send(pass).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
+
+// which pixels the next pass samples, None once the render is done:
+// every pixel has converged or reached `max_samples`, or time ran out
+fn next_pass(film: &Film, config: &Config, start: Instant) -> Option<Vec<bool>> {
+    if let Some(time_budget) = config.time_budget {
+        if start.elapsed() >= time_budget {
+            return None;
+        }
+    }
+
+    let unfinished = |samples: i32| samples < config.max_samples;
+    let trusted = |samples: i32| samples >= config.min_samples.max(2);
+
+    // without a threshold to meet, a time budget is best spent on the noisiest pixels
+    let threshold = match (config.noise_threshold, config.time_budget) {
+        (Some(threshold), _) => Some(threshold),
+        (None, Some(_)) => {
+            let errors: Vec<f64> = film
+                .pixels
+                .iter()
+                .filter(|pixel| unfinished(pixel.samples) && trusted(pixel.samples))
+                .map(|pixel| pixel.relative_error())
+                .collect();
+            match errors.is_empty() {
+                true => None,
+                false => Some(errors.iter().sum::<f64>() / errors.len() as f64),
+            }
+        }
+        (None, None) => None,
+    };
+
+    let active: Vec<bool> = film
+        .pixels
+        .iter()
+        .map(|pixel| {
+            unfinished(pixel.samples)
+                && match threshold {
+                    Some(threshold) if trusted(pixel.samples) => {
+                        pixel.relative_error() >= threshold
+                    }
+                    _ => true,
+                }
+        })
+        .collect();
+
+    active.contains(&true).then_some(active)
+}
+
+// adds one sample to every `active` pixel of the film,
+// returns false if the pass was cancelled before it finished
+fn render_pass(
+    world: &World,
+    camera: &Camera,
+    config: &Config,
+    film: &mut Film,
+    active: &[bool],
+    cancelled: &AtomicBool,
+) -> bool {
+    // each row is an independent unit of work, spread across the rayon pool
+    film.pixels
+        .par_chunks_mut(config.width)
+        .zip(active.par_chunks(config.width))
+        .enumerate()
+        .for_each(|(row, (pixels, active))| {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            let mut sampler = config
+                .sampler
+                .create(config.max_samples.max(1) as usize, config.seed);
+
+            for (col, pixel) in pixels.iter_mut().enumerate() {
+                if !active[col] {
+                    continue;
+                }
+                // the pixel's own sample count is its index into its sequence
+                sampler.start_pixel_sample((col, row), pixel.samples as u64);
+                let (jitter_u, jitter_v) = sampler.get_2d();
+                let u = (col as f64 + jitter_u) / (config.width - 1) as f64;
+                let v = ((config.height - row - 1) as f64 + jitter_v) / (config.height - 1) as f64;
+                let ray = camera.ray_for(u, v, &mut *sampler);
+                pixel.add(ray_color(
+                    &ray,
+                    world,
+                    config.max_depth,
+                    None,
+                    &mut *sampler,
+                ));
+            }
+        });
+
+    if cancelled.load(Ordering::Relaxed) {
+        return false;
+    }
+    film.passes += 1;
+
+    true
+}
+
+// `scatter_pdf` is the density the bsdf picked this ray's direction with,
+// None for camera rays and specular bounces, which light sampling can't reach
+fn ray_color(
+    ray: &Ray,
+    world: &World,
+    depth: usize,
+    scatter_pdf: Option<f64>,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    if depth == 0 {
+        return Color(0., 0., 0.);
+    }
+    if let Some(hit_record) = world.hit(ray, 0.001, f64::INFINITY) {
+        // light sampling could have found this too, weigh the two against each other
+        let weight = match scatter_pdf {
+            Some(pdf) => power_heuristic(pdf, world.light_pdf(&ray.origin, &ray.direction)),
+            None => 1.,
+        };
+        let emitted = weight * hit_record.material.emitted(&hit_record);
+
+        match (*hit_record.material).scatter(ray, &hit_record, sampler) {
+            Some(scatter_record) if scatter_record.is_specular => {
+                return emitted
+                    + scatter_record.attenuation
+                        * ray_color(&scatter_record.ray, world, depth - 1, None, sampler)
+            }
+            Some(scatter_record) => {
+                return emitted
+                    + direct_light(ray, &hit_record, world, sampler)
+                    + scatter_record.attenuation
+                        * ray_color(
+                            &scatter_record.ray,
+                            world,
+                            depth - 1,
+                            Some(scatter_record.pdf),
+                            sampler,
+                        )
+            }
+            None => return emitted,
+        }
+    }
+
+    // the environment may have been sampled as a light as well
+    let weight = match scatter_pdf {
+        Some(pdf) => power_heuristic(pdf, world.light_pdf(&ray.origin, &ray.direction)),
+        None => 1.,
+    };
+    weight * world.environment(&ray.direction)
+}
+
+// next event estimation: one shadow ray towards a randomly picked light,
+// weighted against the bsdf having sampled the same direction
+fn direct_light(
+    ray: &Ray,
+    hit_record: &HitRecord,
+    world: &World,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let origin = hit_record.hit_point;
+    let (direction, pdf) = match world.sample_light(&origin, sampler) {
+        Some(sample) => sample,
+        None => return Color(0., 0., 0.),
+    };
+    let bsdf = hit_record.material.eval(ray, hit_record, &direction);
+    if pdf <= 0. || bsdf.near_zero() {
+        return Color(0., 0., 0.);
+    }
+    let weight = power_heuristic(pdf, hit_record.material.pdf(ray, hit_record, &direction));
+
+    // whatever the shadow ray reaches first decides what light arrives
+    let shadow_ray = Ray {
+        origin,
+        direction,
+        time: ray.time,
+    };
+    let radiance = match world.hit(&shadow_ray, 0.001, f64::INFINITY) {
+        Some(light_hit) => light_hit.material.emitted(&light_hit),
+        None => world.environment(&direction),
+    };
+    weight * bsdf * radiance / pdf
+}
+
+// multiple importance sampling weight for a sample drawn with `pdf`
+// Veach - "Optimally Combining Sampling Techniques for Monte Carlo Rendering", 1995
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0. || !a.is_finite() {
+        return 1.;
+    }
+    a / (a + b)
+}
@@ -0,0 +1,104 @@
+use std::f64::consts::PI;
+
+// Real roots of low degree polynomials, in no particular order. Repeated
+// roots may come back once or twice.
+
+// a x² + b x + c, without the cancellation of the textbook formula
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0. {
+        return match b == 0. {
+            true => vec![],
+            false => vec![-c / b],
+        };
+    }
+
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return vec![];
+    }
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    match q == 0. {
+        // b and c are both zero
+        true => vec![0., 0.],
+        false => vec![q / a, c / q],
+    }
+}
+
+// x³ + a x² + b x + c
+// Press et al. - "Numerical Recipes", 5.6
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3. * b) / 9.;
+    let r = (2. * a * a * a - 9. * a * b + 27. * c) / 54.;
+    let shift = a / 3.;
+
+    if r * r < q * q * q {
+        // three real roots
+        let theta = (r / (q * q * q).sqrt()).clamp(-1., 1.).acos();
+        let scale = -2. * q.sqrt();
+        return vec![
+            scale * (theta / 3.).cos() - shift,
+            scale * ((theta + 2. * PI) / 3.).cos() - shift,
+            scale * ((theta - 2. * PI) / 3.).cos() - shift,
+        ];
+    }
+
+    let big_a = -(r.abs() + (r * r - q * q * q).sqrt()).cbrt().copysign(r);
+    let big_b = match big_a == 0. {
+        true => 0.,
+        false => q / big_a,
+    };
+    vec![big_a + big_b - shift]
+}
+
+// x⁴ + a x³ + b x² + c x + d, by Ferrari's method followed by Newton
+// iterations on the original polynomial, which cleans up most of the
+// precision Ferrari loses when roots are close together
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // depressed quartic y⁴ + p y² + q y + r with x = y - a / 4
+    let shift = a / 4.;
+    let a2 = a * a;
+    let p = b - 3. * a2 / 8.;
+    let q = c - a * b / 2. + a2 * a / 8.;
+    let r = d - a * c / 4. + a2 * b / 16. - 3. * a2 * a2 / 256.;
+
+    let mut roots = if q.abs() < 1e-12 * (1. + p.abs() + r.abs()) {
+        // biquadratic, a quadratic in y²
+        solve_quadratic(1., p, r)
+            .into_iter()
+            .filter(|&z| z >= 0.)
+            .flat_map(|z| [z.sqrt(), -z.sqrt()])
+            .collect()
+    } else {
+        // the resolvent cubic 8 m³ + 8 p m² + (2 p² - 8 r) m - q² always has
+        // a positive root, which splits the quartic into two quadratics
+        let m = solve_cubic(p, p * p / 4. - r, -q * q / 8.)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0. {
+            return vec![];
+        }
+        let s = (2. * m).sqrt();
+        let mut roots = solve_quadratic(1., s, p / 2. + m - q / (2. * s));
+        roots.extend(solve_quadratic(1., -s, p / 2. + m + q / (2. * s)));
+        roots
+    };
+
+    for root in roots.iter_mut() {
+        *root -= shift;
+        for _ in 0..3 {
+            let x = *root;
+            let value = (((x + a) * x + b) * x + c) * x + d;
+            let slope = ((4. * x + 3. * a) * x + 2. * b) * x + c;
+            if slope == 0. {
+                break;
+            }
+            let step = value / slope;
+            if !step.is_finite() {
+                break;
+            }
+            *root = x - step;
+        }
+    }
+
+    roots
+}
@@ -0,0 +1,376 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Source of the random numbers a path consumes. Each call takes the next
+/// dimension of the current sample, so samplers that spread their points
+/// well get the pixel position, then the lens, time and each bounce, in
+/// that order. Every value only depends on the seed, pixel, sample index and
+/// dimension, never on which thread asks or in what order.
+pub trait Sampler {
+    // begins sample `sample_index` of pixel (x, y), back at the first dimension
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: u64);
+
+    // uniform in [0, 1)
+    fn get_1d(&mut self) -> f64;
+    // uniform in [0, 1)²
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    pub const ALL: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    // `samples_per_pixel` is only a hint for the stratified sampler,
+    // which spreads that many samples over its strata
+    pub fn create(&self, samples_per_pixel: usize, seed: u64) -> Box<dyn Sampler> {
+        let state = SampleState::new(seed);
+        match self {
+            SamplerKind::Independent => Box::new(Independent { state }),
+            SamplerKind::Stratified => Box::new(Stratified {
+                samples_per_pixel: samples_per_pixel.max(1) as u64,
+                state,
+            }),
+            SamplerKind::Halton => Box::new(Halton { state }),
+            SamplerKind::Sobol => Box::new(Sobol { state }),
+        }
+    }
+}
+
+impl fmt::Display for SamplerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<SamplerKind, ()> {
+        SamplerKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == name)
+            .ok_or(())
+    }
+}
+
+// where in the sequence the samplers are
+struct SampleState {
+    // the render's seed, picks a whole different set of images
+    seed: u64,
+    // hash of the seed and pixel, decorrelates neighbouring pixels
+    pixel_seed: u64,
+    sample_index: u64,
+    dimension: u64,
+}
+
+impl SampleState {
+    fn new(seed: u64) -> SampleState {
+        SampleState {
+            seed,
+            pixel_seed: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start(&mut self, pixel: (usize, usize), sample_index: u64) {
+        self.pixel_seed = hash(&[self.seed, pixel.0 as u64, pixel.1 as u64]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    // seed for the dimension about to be used, then moves past it
+    fn next_dimension(&mut self, count: u64) -> (u64, u64) {
+        let dimension = self.dimension;
+        self.dimension += count;
+        (dimension, hash(&[self.pixel_seed, dimension]))
+    }
+
+    // uniform value for when a sampler has nothing better to offer
+    fn uniform(&self, dimension_seed: u64, salt: u64) -> f64 {
+        to_unit(hash(&[dimension_seed, self.sample_index, salt]))
+    }
+}
+
+// Independent
+
+// plain pseudo-random numbers, with no structure between samples
+pub struct Independent {
+    state: SampleState,
+}
+
+impl Sampler for Independent {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: u64) {
+        self.state.start(pixel, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (_, seed) = self.state.next_dimension(1);
+        self.state.uniform(seed, 0)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (_, seed) = self.state.next_dimension(2);
+        (self.state.uniform(seed, 0), self.state.uniform(seed, 1))
+    }
+}
+
+// Stratified
+
+// every dimension is cut into as many strata as there are samples per pixel
+// (a grid of about as many for pairs), each sample jitters inside its own
+// stratum, handed out in a shuffled order that differs per pixel and dimension
+pub struct Stratified {
+    samples_per_pixel: u64,
+    state: SampleState,
+}
+
+impl Stratified {
+    fn stratum(&self, count: u64, seed: u64) -> Option<u64> {
+        match self.state.sample_index < count {
+            true => Some(permutation_element(self.state.sample_index, count, seed)),
+            // more samples than strata, nothing left to stratify
+            false => None,
+        }
+    }
+}
+
+impl Sampler for Stratified {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: u64) {
+        self.state.start(pixel, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (_, seed) = self.state.next_dimension(1);
+        let jitter = self.state.uniform(seed, 0);
+        match self.stratum(self.samples_per_pixel, seed) {
+            Some(stratum) => (stratum as f64 + jitter) / self.samples_per_pixel as f64,
+            None => jitter,
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (_, seed) = self.state.next_dimension(2);
+        let jitter = (self.state.uniform(seed, 0), self.state.uniform(seed, 1));
+
+        let columns = (self.samples_per_pixel as f64).sqrt().ceil() as u64;
+        let rows = self.samples_per_pixel.div_ceil(columns);
+        match self.stratum(columns * rows, seed) {
+            Some(stratum) => (
+                ((stratum % columns) as f64 + jitter.0) / columns as f64,
+                ((stratum / columns) as f64 + jitter.1) / rows as f64,
+            ),
+            None => jitter,
+        }
+    }
+}
+
+// Halton
+
+// the first primes, one base per dimension, later dimensions fall back to
+// hashed values since large bases spread poorly anyway
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+// the Halton sequence of each pixel, decorrelated from its neighbours by
+// random digit permutations
+// Kollig and Keller - "Efficient Multidimensional Sampling", 2002
+pub struct Halton {
+    state: SampleState,
+}
+
+impl Halton {
+    fn value(&self, dimension: u64, seed: u64) -> f64 {
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => scrambled_radical_inverse(base, self.state.sample_index, seed),
+            None => self.state.uniform(seed, dimension),
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: u64) {
+        self.state.start(pixel, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (dimension, seed) = self.state.next_dimension(1);
+        self.value(dimension, seed)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (dimension, seed) = self.state.next_dimension(2);
+        (
+            self.value(dimension, seed),
+            self.value(dimension + 1, hash(&[seed, 1])),
+        )
+    }
+}
+
+// digits of `index` in `base` mirrored around the point, each digit position
+// permuted on its own
+fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64) -> f64 {
+    let inverse_base = 1. / base as f64;
+    let mut weight = 1.;
+    let mut value = 0.;
+    let mut position = 0;
+    // permuted zeros past the last digit still count, until they vanish
+    while 1. - (base - 1) as f64 * weight < 1. {
+        let digit = index % base;
+        index /= base;
+        weight *= inverse_base;
+        let permuted = permutation_element(digit, base, hash(&[seed, position]));
+        value += permuted as f64 * weight;
+        position += 1;
+    }
+
+    value.min(ONE_MINUS_EPSILON)
+}
+
+// Sobol
+
+// the first two dimensions of Sobol's sequence, Owen scrambled, with a fresh
+// scramble and index shuffle for every pair of dimensions a path asks for
+// Burley - "Practical Hash-based Owen Scrambling", JCGT 2020
+pub struct Sobol {
+    state: SampleState,
+}
+
+impl Sobol {
+    fn point(&mut self, count: u64) -> (f64, f64) {
+        let (_, seed) = self.state.next_dimension(count);
+        let index = nested_uniform_scramble(self.state.sample_index as u32, seed as u32);
+        let (x, y) = sobol_2d(index);
+        (
+            nested_uniform_scramble(x, hash(&[seed, 0]) as u32) as f64 / 4294967296.,
+            nested_uniform_scramble(y, hash(&[seed, 1]) as u32) as f64 / 4294967296.,
+        )
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample_index: u64) {
+        self.state.start(pixel, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.point(1).0
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        self.point(2)
+    }
+}
+
+// fixed point with the first bit worth a half: the van der Corput sequence and
+// the dimension built on the polynomial x + 1
+fn sobol_2d(index: u32) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    let mut direction: u32 = 1 << 31;
+    for bit in 0..32 {
+        if (index >> bit) & 1 == 1 {
+            x ^= 1 << (31 - bit);
+            y ^= direction;
+        }
+        direction ^= direction >> 1;
+    }
+    (x, y)
+}
+
+// a random permutation of the bits of `x` where every bit only depends on
+// the ones before it, most significant first
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// the same for the bits from the least significant up
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+// Hashing
+
+const ONE_MINUS_EPSILON: f64 = 1. - f64::EPSILON / 2.;
+
+// splitmix64's finalizer folded over the values
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |state, &value| {
+        let mut z = (state ^ value).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
+}
+
+// the top 53 bits as a float in [0, 1)
+pub fn to_unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// element `index` of a random permutation of 0..count picked by `seed`,
+// without building the permutation
+// Kensler - "Correlated Multi-Jittered Sampling", 2013
+fn permutation_element(index: u64, count: u64, seed: u64) -> u64 {
+    let (count, p) = (count as u32, seed as u32);
+    let mut w = count.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // cycle walking: repeat until the result lands inside the range
+    let mut i = index as u32;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < count {
+            break;
+        }
+    }
+
+    (i as u64 + p as u64) % count as u64
+}
@@ -0,0 +1,1073 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::csg::{Csg, Operation};
+use crate::environment::{Constant, Environment, EnvironmentMap, Gradient, Sky};
+use crate::image::Image;
+use crate::medium::ConstantMedium;
+use crate::mesh::{Triangle, TriangleMesh};
+use crate::obj;
+use crate::render::Config;
+use crate::sampler::SamplerKind;
+use crate::texture::{Checker, ImageTexture, Noise, NoiseKind, Perlin, SolidColor, Texture};
+use crate::tonemap::{ToneMapper, ToneMapping};
+use crate::vec3::{Color, Point3, Transform, Vec3};
+use crate::world::{
+    Cone, Cuboid, Cylinder, Dielectric, DiffuseLight, Disk, Hittable, Instance, Isotropic,
+    Lambertian, Material, Metal, MovingSphere, Plane, Rect, Sphere, Torus, World,
+};
+
+/// Scene used when no file is given on the command line.
+pub const DEFAULT_SCENE: &str = include_str!("../scenes/default.ron");
+
+pub struct Scene {
+    pub world: World,
+    pub camera: Camera,
+    pub config: Config,
+    // the parameters of the world's environment when it's a daylight sky
+    pub sky: Option<Sky>,
+    pub tone_mapping: ToneMapping,
+}
+
+// Errors
+
+pub struct SceneError {
+    pub source_name: String,
+    // 1-based, only known for errors found while parsing
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.source_name, line, column, self.message
+            ),
+            _ => write!(f, "{}: {}", self.source_name, self.message),
+        }
+    }
+}
+
+// Loading
+
+pub fn load(path: &Path) -> Result<Scene, SceneError> {
+    let source_name = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|err| SceneError {
+        source_name: source_name.clone(),
+        line: None,
+        column: None,
+        message: err.to_string(),
+    })?;
+
+    let directory = path.parent().unwrap_or_else(|| Path::new("."));
+    parse(&source, &source_name, directory)
+}
+
+/// `directory` is what relative paths inside the scene are resolved against.
+pub fn parse(source: &str, source_name: &str, directory: &Path) -> Result<Scene, SceneError> {
+    let desc: SceneDesc = ron::from_str(source).map_err(|err| SceneError {
+        source_name: source_name.to_string(),
+        line: Some(err.position.line),
+        column: Some(err.position.col),
+        message: err.code.to_string(),
+    })?;
+
+    desc.build(directory).map_err(|message| SceneError {
+        source_name: source_name.to_string(),
+        line: None,
+        column: None,
+        message,
+    })
+}
+
+// File format
+
+#[derive(Deserialize)]
+#[serde(rename = "Scene", deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    settings: SettingsDesc,
+    camera: CameraDesc,
+    // what rays that leave the scene see
+    #[serde(default)]
+    environment: EnvironmentDesc,
+    #[serde(default)]
+    textures: BTreeMap<String, TextureDesc>,
+    materials: BTreeMap<String, MaterialDesc>,
+    // objects built once and placed any number of times by `Instance`s
+    #[serde(default)]
+    shapes: BTreeMap<String, ObjectDesc>,
+    objects: Vec<ObjectDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SettingsDesc {
+    width: usize,
+    height: usize,
+    // the most per pixel
+    samples: i32,
+    // adaptive sampling, see `Config`
+    min_samples: i32,
+    noise_threshold: Option<f64>,
+    // in seconds
+    time_budget: Option<f64>,
+    max_depth: usize,
+    sampler: SamplerDesc,
+    seed: u64,
+    tone_mapper: ToneMapperDesc,
+    // in stops
+    exposure: f64,
+    // for ExtendedReinhard
+    white_point: f64,
+}
+
+impl Default for SettingsDesc {
+    fn default() -> Self {
+        let tone_mapping = ToneMapping::default();
+        SettingsDesc {
+            width: 480,
+            height: 270,
+            samples: 100,
+            min_samples: 16,
+            noise_threshold: None,
+            time_budget: None,
+            max_depth: 50,
+            sampler: SamplerDesc::Sobol,
+            seed: 0,
+            tone_mapper: ToneMapperDesc::Clamp,
+            exposure: tone_mapping.exposure,
+            white_point: tone_mapping.white_point,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum SamplerDesc {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+#[derive(Deserialize)]
+enum ToneMapperDesc {
+    Clamp,
+    Reinhard,
+    ExtendedReinhard,
+    Aces,
+    Agx,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    origin: Point3,
+    look_at: Point3,
+    #[serde(default = "default_vup")]
+    vup: Vec3,
+    vfov: f64,
+    #[serde(default = "default_focal_length")]
+    focal_length: f64,
+    #[serde(default)]
+    aperture: f64,
+    // defaults to the distance to `look_at`
+    #[serde(default)]
+    focus_distance: Option<f64>,
+    #[serde(default)]
+    shutter_open: f64,
+    #[serde(default)]
+    shutter_close: f64,
+}
+
+fn default_vup() -> Vec3 {
+    Vec3(0., 1., 0.)
+}
+
+fn default_focal_length() -> f64 {
+    1.
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum EnvironmentDesc {
+    Gradient {
+        #[serde(default = "default_gradient_bottom")]
+        bottom: Color,
+        #[serde(default = "default_gradient_top")]
+        top: Color,
+    },
+    Constant {
+        color: Color,
+    },
+    // equirectangular, relative to the scene file, with -z in the middle
+    Image {
+        path: String,
+        // degrees counterclockwise seen from above
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    // Preetham daylight, angles in degrees, the azimuth from -z towards +x
+    Sky {
+        elevation: f64,
+        #[serde(default)]
+        azimuth: f64,
+        #[serde(default = "default_turbidity")]
+        turbidity: f64,
+    },
+}
+
+impl Default for EnvironmentDesc {
+    fn default() -> Self {
+        EnvironmentDesc::Gradient {
+            bottom: default_gradient_bottom(),
+            top: default_gradient_top(),
+        }
+    }
+}
+
+fn default_gradient_bottom() -> Color {
+    Color(1., 1., 1.)
+}
+
+fn default_gradient_top() -> Color {
+    Color(0.5, 0.7, 1.)
+}
+
+fn default_intensity() -> f64 {
+    1.
+}
+
+fn default_turbidity() -> f64 {
+    3.
+}
+
+// a plain color or the name of an entry in `textures`
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "expected a color or a texture name")]
+enum TextureRef {
+    Color(Color),
+    Named(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum TextureDesc {
+    // world space cubes with sides `size`
+    Checker {
+        size: f64,
+        even: TextureRef,
+        odd: TextureRef,
+    },
+    Noise {
+        kind: NoiseKindDesc,
+        scale: f64,
+        #[serde(default = "default_noise_color")]
+        color: Color,
+        #[serde(default)]
+        seed: u64,
+    },
+    // .png, .ppm or .hdr, relative to the scene file
+    Image {
+        path: String,
+    },
+}
+
+#[derive(Deserialize)]
+enum NoiseKindDesc {
+    Perlin,
+    Turbulence,
+    Marble,
+}
+
+fn default_time1() -> f64 {
+    1.
+}
+
+fn default_noise_color() -> Color {
+    Color(1., 1., 1.)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: TextureRef },
+    Metal { albedo: TextureRef, fuzz: f64 },
+    Dielectric { ior: f64 },
+    DiffuseLight { emit: Color },
+    // for media
+    Isotropic { albedo: TextureRef },
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: Point3,
+        radius: f64,
+        material: String,
+    },
+    // at center0 until time0, then in a straight line to center1 at time1
+    MovingSphere {
+        center0: Point3,
+        center1: Point3,
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_time1")]
+        time1: f64,
+        radius: f64,
+        material: String,
+    },
+    Plane {
+        point: Point3,
+        normal: Vec3,
+        material: String,
+    },
+    Disk {
+        center: Point3,
+        normal: Vec3,
+        radius: f64,
+        material: String,
+    },
+    // axis-aligned, `min` and `max` share the coordinate `normal` points along
+    Rect {
+        min: Point3,
+        max: Point3,
+        normal: Vec3,
+        material: String,
+    },
+    // axis-aligned
+    #[serde(rename = "Box")]
+    Cuboid {
+        min: Point3,
+        max: Point3,
+        material: String,
+    },
+    Cylinder {
+        base: Point3,
+        top: Point3,
+        radius: f64,
+        material: String,
+    },
+    Cone {
+        base: Point3,
+        apex: Point3,
+        radius: f64,
+        material: String,
+    },
+    Torus {
+        center: Point3,
+        #[serde(default = "default_vup")]
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: (Point3, Point3, Point3),
+        material: String,
+    },
+    Mesh {
+        positions: Vec<Point3>,
+        #[serde(default)]
+        normals: Vec<Vec3>,
+        #[serde(default)]
+        uvs: Vec<(f64, f64)>,
+        triangles: Vec<(u32, u32, u32)>,
+        material: String,
+    },
+    // materials come from the .mtl files unless one is given here
+    Obj {
+        path: String,
+        #[serde(default)]
+        material: Option<String>,
+    },
+    // one of `shapes`, transformed by each step in order
+    Instance {
+        shape: String,
+        #[serde(default)]
+        transform: Vec<TransformDesc>,
+    },
+    // smoke or fog filling a closed, convex solid
+    Medium {
+        boundary: Box<ObjectDesc>,
+        density: f64,
+        material: String,
+    },
+    // both sides have to be closed solids: spheres, closed meshes,
+    // other CSG nodes or instances of any of those
+    Union {
+        left: Box<ObjectDesc>,
+        right: Box<ObjectDesc>,
+    },
+    Intersection {
+        left: Box<ObjectDesc>,
+        right: Box<ObjectDesc>,
+    },
+    // `right` cut out of `left`
+    Difference {
+        left: Box<ObjectDesc>,
+        right: Box<ObjectDesc>,
+    },
+}
+
+#[derive(Clone, Deserialize)]
+enum TransformDesc {
+    Translate(Vec3),
+    Scale(Vec3),
+    // counterclockwise looking down the axis
+    Rotate { axis: Vec3, degrees: f64 },
+}
+
+impl SceneDesc {
+    fn build(self, directory: &Path) -> Result<Scene, String> {
+        let settings = self.settings;
+        if settings.width < 2 || settings.height < 2 {
+            return Err("settings: image must be at least 2x2 pixels".to_string());
+        }
+        if settings.samples < 1 {
+            return Err("settings.samples: must be at least 1".to_string());
+        }
+        if settings.min_samples < 2 {
+            return Err("settings.min_samples: must be at least 2".to_string());
+        }
+        if settings
+            .noise_threshold
+            .is_some_and(|threshold| threshold <= 0.)
+        {
+            return Err("settings.noise_threshold: must be positive".to_string());
+        }
+        let time_budget = match settings.time_budget {
+            Some(seconds) if seconds <= 0. || !seconds.is_finite() => {
+                return Err("settings.time_budget: must be a positive number of seconds".to_string())
+            }
+            Some(seconds) => Some(Duration::from_secs_f64(seconds)),
+            None => None,
+        };
+        let config = Config {
+            width: settings.width,
+            height: settings.height,
+            max_samples: settings.samples,
+            min_samples: settings.min_samples,
+            noise_threshold: settings.noise_threshold,
+            time_budget,
+            max_depth: settings.max_depth,
+            sampler: match settings.sampler {
+                SamplerDesc::Independent => SamplerKind::Independent,
+                SamplerDesc::Stratified => SamplerKind::Stratified,
+                SamplerDesc::Halton => SamplerKind::Halton,
+                SamplerDesc::Sobol => SamplerKind::Sobol,
+            },
+            seed: settings.seed,
+        };
+        if config.is_adaptive() && config.min_samples > config.max_samples {
+            return Err(
+                "settings.min_samples: must not exceed samples when sampling adaptively"
+                    .to_string(),
+            );
+        }
+        if settings.white_point <= 0. {
+            return Err("settings.white_point: must be positive".to_string());
+        }
+        let tone_mapping = ToneMapping {
+            tone_mapper: match settings.tone_mapper {
+                ToneMapperDesc::Clamp => ToneMapper::Clamp,
+                ToneMapperDesc::Reinhard => ToneMapper::Reinhard,
+                ToneMapperDesc::ExtendedReinhard => ToneMapper::ExtendedReinhard,
+                ToneMapperDesc::Aces => ToneMapper::Aces,
+                ToneMapperDesc::Agx => ToneMapper::Agx,
+            },
+            exposure: settings.exposure,
+            white_point: settings.white_point,
+        };
+
+        // Textures
+        let mut textures = Textures {
+            descs: &self.textures,
+            built: BTreeMap::new(),
+            directory,
+        };
+        for name in self.textures.keys() {
+            textures.get(format!("textures.{}", name), name, 0)?;
+        }
+
+        // Materials
+        let mut materials: BTreeMap<&str, Arc<dyn Material>> = BTreeMap::new();
+        for (name, desc) in &self.materials {
+            let material = desc.build(&format!("materials.{}", name), &mut textures)?;
+            materials.insert(name.as_str(), material);
+        }
+
+        // Shapes
+        let mut shapes: BTreeMap<&str, Arc<dyn Hittable>> = BTreeMap::new();
+        for (name, desc) in &self.shapes {
+            let field = format!("shapes.{}", name);
+            if let ObjectDesc::Instance { .. } = desc {
+                return Err(format!("{}: shapes can't be instances themselves", field));
+            }
+            let mut parts = desc.clone().build(&field, &materials, &shapes, directory)?;
+            let shape = match parts.len() {
+                1 => parts.remove(0),
+                _ => Arc::new(Bvh::new(parts)),
+            };
+            shapes.insert(name.as_str(), shape);
+        }
+
+        // world
+        let mut objects: Vec<Arc<dyn Hittable>> = vec![];
+        let mut lights: Vec<Arc<dyn Hittable>> = vec![];
+        for (idx, object) in self.objects.into_iter().enumerate() {
+            let material_name = match &object {
+                ObjectDesc::Instance { shape, .. } => {
+                    self.shapes.get(shape).and_then(ObjectDesc::material_name)
+                }
+                _ => object.material_name(),
+            };
+            let is_light = material_name
+                .and_then(|name| self.materials.get(name))
+                .is_some_and(MaterialDesc::is_emissive);
+
+            let built =
+                object.build(&format!("objects[{}]", idx), &materials, &shapes, directory)?;
+
+            if is_light {
+                lights.extend(built.iter().cloned());
+            }
+            objects.extend(built);
+        }
+
+        let mut sky = None;
+        let environment: Arc<dyn Environment> = match self.environment {
+            EnvironmentDesc::Gradient { bottom, top } => Arc::new(Gradient { bottom, top }),
+            EnvironmentDesc::Constant { color } => Arc::new(Constant { color }),
+            EnvironmentDesc::Image {
+                path,
+                rotation,
+                intensity,
+            } => {
+                let image = Image::load(&directory.join(path))
+                    .map_err(|err| format!("environment.path: {}", err))?;
+                if image.width == 0 || image.height == 0 {
+                    return Err("environment.path: image is empty".to_string());
+                }
+                if intensity < 0. {
+                    return Err("environment.intensity: must not be negative".to_string());
+                }
+                Arc::new(EnvironmentMap::new(image, rotation, intensity))
+            }
+            EnvironmentDesc::Sky {
+                elevation,
+                azimuth,
+                turbidity,
+            } => {
+                if !(0. ..=90.).contains(&elevation) {
+                    return Err("environment.elevation: must be between 0 and 90".to_string());
+                }
+                // the range Preetham et al. fit their model over
+                if !(2. ..=10.).contains(&turbidity) {
+                    return Err("environment.turbidity: must be between 2 and 10".to_string());
+                }
+                let built = Sky::new(elevation, azimuth, turbidity);
+                sky = Some(built.clone());
+                Arc::new(built)
+            }
+        };
+
+        let world = World::new(objects, lights, environment);
+
+        // camera
+        let desc = self.camera;
+        let direction = desc.origin - desc.look_at;
+        if direction.near_zero() {
+            return Err("camera.look_at: must differ from camera.origin".to_string());
+        }
+        if desc.focal_length <= 0. {
+            return Err("camera.focal_length: must be positive".to_string());
+        }
+        if desc.aperture < 0. {
+            return Err("camera.aperture: must not be negative".to_string());
+        }
+        let focus_distance = desc.focus_distance.unwrap_or_else(|| direction.length());
+        if focus_distance <= 0. {
+            return Err("camera.focus_distance: must be positive".to_string());
+        }
+        let mut camera = Camera::new(
+            desc.origin,
+            direction.unit(),
+            desc.vup.unit(),
+            desc.vfov,
+            desc.focal_length,
+            config.width as f64 / config.height as f64,
+        );
+        if desc.shutter_close < desc.shutter_open {
+            return Err("camera.shutter_close: must not be before shutter_open".to_string());
+        }
+        camera.aperture = desc.aperture;
+        camera.focus_distance = focus_distance;
+        camera.shutter_open = desc.shutter_open;
+        camera.shutter_close = desc.shutter_close;
+
+        Ok(Scene {
+            world,
+            camera,
+            config,
+            sky,
+            tone_mapping,
+        })
+    }
+}
+
+impl ObjectDesc {
+    fn build(
+        self,
+        field: &str,
+        materials: &BTreeMap<&str, Arc<dyn Material>>,
+        shapes: &BTreeMap<&str, Arc<dyn Hittable>>,
+        directory: &Path,
+    ) -> Result<Vec<Arc<dyn Hittable>>, String> {
+        let material = |field: String, name: &str| {
+            materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("{}: unknown material \"{}\"", field, name))
+        };
+        let operand = |desc: ObjectDesc, field: String| {
+            let mut parts = desc.build(&field, materials, shapes, directory)?;
+            match parts.len() == 1 && parts[0].is_solid() {
+                true => Ok(parts.remove(0)),
+                false => Err(format!("{}: must be a single closed solid", field)),
+            }
+        };
+        let operands = |left: ObjectDesc, right: ObjectDesc| {
+            Ok::<_, String>((
+                operand(left, format!("{}.left", field))?,
+                operand(right, format!("{}.right", field))?,
+            ))
+        };
+
+        let built: Vec<Arc<dyn Hittable>> = match self {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material: material_name,
+            } => {
+                if radius <= 0. {
+                    return Err(format!("{}.radius: must be positive", field));
+                }
+                vec![Arc::new(Sphere {
+                    center,
+                    radius,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material: material_name,
+            } => {
+                if radius <= 0. {
+                    return Err(format!("{}.radius: must be positive", field));
+                }
+                if time1 < time0 {
+                    return Err(format!("{}.time1: must not be before time0", field));
+                }
+                vec![Arc::new(MovingSphere {
+                    center0,
+                    center1,
+                    time0,
+                    time1,
+                    radius,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Plane {
+                point,
+                normal,
+                material: material_name,
+            } => {
+                if normal.near_zero() {
+                    return Err(format!("{}.normal: must not be zero", field));
+                }
+                vec![Arc::new(Plane {
+                    point,
+                    normal: normal.unit(),
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Disk {
+                center,
+                normal,
+                radius,
+                material: material_name,
+            } => {
+                if normal.near_zero() {
+                    return Err(format!("{}.normal: must not be zero", field));
+                }
+                if radius <= 0. {
+                    return Err(format!("{}.radius: must be positive", field));
+                }
+                vec![Arc::new(Disk {
+                    center,
+                    normal: normal.unit(),
+                    radius,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Rect {
+                min,
+                max,
+                normal,
+                material: material_name,
+            } => {
+                let axes: Vec<usize> = (0..3).filter(|&axis| normal[axis] != 0.).collect();
+                let axis = match axes[..] {
+                    [axis] => axis,
+                    _ => {
+                        return Err(format!(
+                            "{}.normal: must point along the x, y or z axis",
+                            field
+                        ))
+                    }
+                };
+                if min[axis] != max[axis] {
+                    return Err(format!(
+                        "{}: min and max must lie in a plane perpendicular to the normal",
+                        field
+                    ));
+                }
+                // corners in any order
+                let bounds = Aabb::new(min, max);
+                vec![Arc::new(Rect {
+                    axis,
+                    facing: normal[axis].signum(),
+                    min: bounds.min,
+                    max: bounds.max,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Cuboid {
+                min,
+                max,
+                material: material_name,
+            } => {
+                if (0..3).any(|axis| min[axis] >= max[axis]) {
+                    return Err(format!("{}.max: must be above min on every axis", field));
+                }
+                vec![Arc::new(Cuboid {
+                    min,
+                    max,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Cylinder {
+                base,
+                top,
+                radius,
+                material: material_name,
+            } => {
+                if (top - base).near_zero() {
+                    return Err(format!("{}.top: must differ from base", field));
+                }
+                if radius <= 0. {
+                    return Err(format!("{}.radius: must be positive", field));
+                }
+                vec![Arc::new(Cylinder {
+                    base,
+                    top,
+                    radius,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Cone {
+                base,
+                apex,
+                radius,
+                material: material_name,
+            } => {
+                if (apex - base).near_zero() {
+                    return Err(format!("{}.apex: must differ from base", field));
+                }
+                if radius <= 0. {
+                    return Err(format!("{}.radius: must be positive", field));
+                }
+                vec![Arc::new(Cone {
+                    base,
+                    apex,
+                    radius,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                material: material_name,
+            } => {
+                if axis.near_zero() {
+                    return Err(format!("{}.axis: must not be zero", field));
+                }
+                if major_radius <= 0. || minor_radius <= 0. {
+                    return Err(format!("{}: radii must be positive", field));
+                }
+                vec![Arc::new(Torus {
+                    center,
+                    axis: axis.unit(),
+                    major_radius,
+                    minor_radius,
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Triangle {
+                vertices: (v0, v1, v2),
+                material: material_name,
+            } => {
+                vec![Arc::new(Triangle {
+                    vertices: [v0, v1, v2],
+                    material: material(format!("{}.material", field), &material_name)?,
+                })]
+            }
+            ObjectDesc::Mesh {
+                positions,
+                normals,
+                uvs,
+                triangles,
+                material: material_name,
+            } => {
+                let mesh = TriangleMesh::new(
+                    positions,
+                    normals,
+                    uvs,
+                    triangles.into_iter().map(|(a, b, c)| [a, b, c]).collect(),
+                    material(format!("{}.material", field), &material_name)?,
+                )
+                .map_err(|err| format!("{}: {}", field, err))?;
+                vec![Arc::new(mesh)]
+            }
+            ObjectDesc::Obj {
+                path,
+                material: material_name,
+            } => {
+                let material_override = match material_name {
+                    Some(name) => Some(material(format!("{}.material", field), &name)?),
+                    None => None,
+                };
+                let fallback = Arc::new(Lambertian {
+                    albedo: Arc::new(SolidColor {
+                        color: Color(0.5, 0.5, 0.5),
+                    }),
+                });
+                let meshes = obj::load(&directory.join(path), fallback, material_override)
+                    .map_err(|err| format!("{}: {}", field, err))?;
+                meshes
+                    .into_iter()
+                    .map(|mesh| Arc::new(mesh) as Arc<dyn Hittable>)
+                    .collect()
+            }
+            ObjectDesc::Instance { shape, transform } => {
+                let object = shapes
+                    .get(shape.as_str())
+                    .cloned()
+                    .ok_or_else(|| format!("{}.shape: unknown shape \"{}\"", field, shape))?;
+
+                let mut combined = Transform::identity();
+                for (step_idx, step) in transform.into_iter().enumerate() {
+                    let step_field = format!("{}.transform[{}]", field, step_idx);
+                    let step = match step {
+                        TransformDesc::Translate(offset) => Transform::translate(offset),
+                        TransformDesc::Scale(factors) => {
+                            if (0..3).any(|axis| factors[axis] == 0.) {
+                                return Err(format!(
+                                    "{}: scale factors must be non-zero",
+                                    step_field
+                                ));
+                            }
+                            Transform::scale(factors)
+                        }
+                        TransformDesc::Rotate { axis, degrees } => {
+                            if axis.near_zero() {
+                                return Err(format!("{}.axis: must not be zero", step_field));
+                            }
+                            Transform::rotate(axis, degrees)
+                        }
+                    };
+                    combined = combined.then(&step);
+                }
+
+                vec![Arc::new(Instance::new(object, combined))]
+            }
+            ObjectDesc::Medium {
+                boundary,
+                density,
+                material: material_name,
+            } => {
+                if density <= 0. {
+                    return Err(format!("{}.density: must be positive", field));
+                }
+                let boundary = operand(*boundary, format!("{}.boundary", field))?;
+                vec![Arc::new(ConstantMedium::new(
+                    boundary,
+                    density,
+                    material(format!("{}.material", field), &material_name)?,
+                ))]
+            }
+            ObjectDesc::Union { left, right } => {
+                let (left, right) = operands(*left, *right)?;
+                vec![Arc::new(Csg::new(Operation::Union, left, right))]
+            }
+            ObjectDesc::Intersection { left, right } => {
+                let (left, right) = operands(*left, *right)?;
+                vec![Arc::new(Csg::new(Operation::Intersection, left, right))]
+            }
+            ObjectDesc::Difference { left, right } => {
+                let (left, right) = operands(*left, *right)?;
+                vec![Arc::new(Csg::new(Operation::Difference, left, right))]
+            }
+        };
+
+        Ok(built)
+    }
+
+    fn material_name(&self) -> Option<&str> {
+        match self {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::MovingSphere { material, .. }
+            | ObjectDesc::Plane { material, .. }
+            | ObjectDesc::Disk { material, .. }
+            | ObjectDesc::Rect { material, .. }
+            | ObjectDesc::Cuboid { material, .. }
+            | ObjectDesc::Cylinder { material, .. }
+            | ObjectDesc::Cone { material, .. }
+            | ObjectDesc::Torus { material, .. }
+            | ObjectDesc::Triangle { material, .. }
+            | ObjectDesc::Mesh { material, .. }
+            | ObjectDesc::Medium { material, .. } => Some(material),
+            ObjectDesc::Obj { material, .. } => material.as_deref(),
+            // depends on the shape
+            ObjectDesc::Instance { .. } => None,
+            // can mix materials
+            ObjectDesc::Union { .. }
+            | ObjectDesc::Intersection { .. }
+            | ObjectDesc::Difference { .. } => None,
+        }
+    }
+}
+
+impl MaterialDesc {
+    // objects made of these get sampled as lights
+    fn is_emissive(&self) -> bool {
+        matches!(self, MaterialDesc::DiffuseLight { .. })
+    }
+
+    fn build(&self, field: &str, textures: &mut Textures) -> Result<Arc<dyn Material>, String> {
+        let albedo = |textures: &mut Textures, albedo: &TextureRef| {
+            textures.resolve(format!("{}.albedo", field), albedo, 0)
+        };
+
+        Ok(match self {
+            MaterialDesc::Lambertian { albedo: texture } => Arc::new(Lambertian {
+                albedo: albedo(textures, texture)?,
+            }),
+            MaterialDesc::Metal {
+                albedo: texture,
+                fuzz,
+            } => Arc::new(Metal {
+                albedo: albedo(textures, texture)?,
+                fuzz: *fuzz,
+            }),
+            MaterialDesc::Dielectric { ior } => Arc::new(Dielectric { ior: *ior }),
+            MaterialDesc::DiffuseLight { emit } => Arc::new(DiffuseLight { emit: *emit }),
+            MaterialDesc::Isotropic { albedo: texture } => Arc::new(Isotropic {
+                albedo: albedo(textures, texture)?,
+            }),
+        })
+    }
+}
+
+// named textures are built once, on first use, so materials and checkers share them
+struct Textures<'a> {
+    descs: &'a BTreeMap<String, TextureDesc>,
+    built: BTreeMap<&'a str, Arc<dyn Texture>>,
+    directory: &'a Path,
+}
+
+impl<'a> Textures<'a> {
+    fn resolve(
+        &mut self,
+        field: String,
+        reference: &TextureRef,
+        depth: usize,
+    ) -> Result<Arc<dyn Texture>, String> {
+        match reference {
+            TextureRef::Color(color) => Ok(Arc::new(SolidColor { color: *color })),
+            TextureRef::Named(name) => self.get(field, name, depth),
+        }
+    }
+
+    fn get(&mut self, field: String, name: &str, depth: usize) -> Result<Arc<dyn Texture>, String> {
+        if let Some(texture) = self.built.get(name) {
+            return Ok(texture.clone());
+        }
+        let (name, desc) = self
+            .descs
+            .get_key_value(name)
+            .ok_or_else(|| format!("{}: unknown texture \"{}\"", field, name))?;
+        // deeper than the number of textures means a checker contains itself
+        if depth > self.descs.len() {
+            return Err(format!("textures.{}: refers to itself", name));
+        }
+
+        let field = format!("textures.{}", name);
+        let texture: Arc<dyn Texture> = match desc {
+            TextureDesc::Checker { size, even, odd } => {
+                if *size <= 0. {
+                    return Err(format!("{}.size: must be positive", field));
+                }
+                Arc::new(Checker {
+                    size: *size,
+                    even: self.resolve(format!("{}.even", field), even, depth + 1)?,
+                    odd: self.resolve(format!("{}.odd", field), odd, depth + 1)?,
+                })
+            }
+            TextureDesc::Noise {
+                kind,
+                scale,
+                color,
+                seed,
+            } => Arc::new(Noise {
+                kind: match kind {
+                    NoiseKindDesc::Perlin => NoiseKind::Perlin,
+                    NoiseKindDesc::Turbulence => NoiseKind::Turbulence,
+                    NoiseKindDesc::Marble => NoiseKind::Marble,
+                },
+                scale: *scale,
+                color: *color,
+                perlin: Perlin::new(*seed),
+            }),
+            TextureDesc::Image { path } => Arc::new(ImageTexture {
+                image: Image::load(&self.directory.join(path))
+                    .map_err(|err| format!("{}.path: {}", field, err))?,
+            }),
+        };
+
+        self.built.insert(name.as_str(), texture.clone());
+        Ok(texture)
+    }
+}
@@ -0,0 +1,182 @@
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::vec3::{Color, Point3, Vec3};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, uv: (f64, f64), point: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    pub color: Color,
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _uv: (f64, f64), _point: &Point3) -> Color {
+        self.color
+    }
+}
+
+// 3D checker board of cubes with sides `size`, independent of the uv mapping
+pub struct Checker {
+    pub size: f64,
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+}
+
+impl Texture for Checker {
+    fn value(&self, uv: (f64, f64), point: &Point3) -> Color {
+        let cell = |coord: f64| (coord / self.size).floor() as i64;
+        match (cell(point.x()) + cell(point.y()) + cell(point.z())).rem_euclid(2) {
+            0 => self.even.value(uv, point),
+            _ => self.odd.value(uv, point),
+        }
+    }
+}
+
+// Noise
+
+pub enum NoiseKind {
+    Perlin,
+    Turbulence,
+    Marble,
+}
+
+pub struct Noise {
+    pub kind: NoiseKind,
+    pub scale: f64,
+    pub color: Color,
+    pub perlin: Perlin,
+}
+
+impl Texture for Noise {
+    fn value(&self, _uv: (f64, f64), point: &Point3) -> Color {
+        let p = self.scale * *point;
+        let intensity = match self.kind {
+            // perlin noise is in [-1, 1]
+            NoiseKind::Perlin => 0.5 * (1. + self.perlin.noise(&p)),
+            NoiseKind::Turbulence => self.perlin.turbulence(&p, 7),
+            // turbulence at the base frequency shifts the phase of `scale` spaced stripes
+            NoiseKind::Marble => {
+                0.5 * (1. + (p.z() + 10. * self.perlin.turbulence(point, 7)).sin())
+            }
+        };
+
+        intensity * self.color
+    }
+}
+
+const POINT_COUNT: usize = 256;
+
+// Perlin gradient noise
+// Shirley - "Ray Tracing: The Next Week", chapter 5
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    // seeded, the same scene always gets the same pattern
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .unit()
+            })
+            .collect();
+        let mut permutation = || {
+            let mut permutation: Vec<usize> = (0..POINT_COUNT).collect();
+            permutation.shuffle(&mut rng);
+            permutation
+        };
+        let permutations = [permutation(), permutation(), permutation()];
+
+        Perlin {
+            gradients,
+            permutations,
+        }
+    }
+
+    pub fn noise(&self, point: &Point3) -> f64 {
+        let floor = [point.x().floor(), point.y().floor(), point.z().floor()];
+        let fraction = [
+            point.x() - floor[0],
+            point.y() - floor[1],
+            point.z() - floor[2],
+        ];
+        // hermite smoothing
+        let smooth = fraction.map(|f| f * f * (3. - 2. * f));
+
+        let mut acc = 0.;
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let hash = (0..3)
+                .map(|axis| {
+                    let lattice = (floor[axis] as i64 + offset[axis] as i64) & 255;
+                    self.permutations[axis][lattice as usize]
+                })
+                .fold(0, |acc, value| acc ^ value);
+
+            let weight = Vec3(
+                fraction[0] - offset[0] as f64,
+                fraction[1] - offset[1] as f64,
+                fraction[2] - offset[2] as f64,
+            );
+            let falloff = (0..3)
+                .map(|axis| match offset[axis] {
+                    1 => smooth[axis],
+                    _ => 1. - smooth[axis],
+                })
+                .product::<f64>();
+
+            acc += falloff * self.gradients[hash].dot(&weight);
+        }
+
+        acc
+    }
+
+    // sum of noise at doubling frequencies and halving amplitudes
+    pub fn turbulence(&self, point: &Point3, depth: usize) -> f64 {
+        let mut acc = 0.;
+        let mut p = *point;
+        let mut weight = 1.;
+
+        for _ in 0..depth {
+            acc += weight * self.noise(&p);
+            weight *= 0.5;
+            p = p * 2.;
+        }
+
+        acc.abs()
+    }
+}
+
+// Image
+
+pub struct ImageTexture {
+    pub image: Image,
+}
+
+impl Texture for ImageTexture {
+    // nearest pixel, (0, 0) is the bottom left corner
+    fn value(&self, uv: (f64, f64), _point: &Point3) -> Color {
+        if self.image.width == 0 || self.image.height == 0 {
+            return Color(0., 1., 1.);
+        }
+
+        let u = uv.0.clamp(0., 1.);
+        let v = 1. - uv.1.clamp(0., 1.);
+        let x = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let y = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+
+        self.image.pixel(x, y)
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::vec3::Color;
+
+/// How linear radiance is squeezed into the [0, 1] a display can show.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ToneMapper {
+    // cuts everything brighter than 1
+    Clamp,
+    // x / (1 + x), never reaches white
+    Reinhard,
+    // reaches white at `ToneMapping::white_point`
+    ExtendedReinhard,
+    // Hill's fit of the ACES reference and output transforms
+    Aces,
+    // Sobotka's AgX, desaturates highlights instead of skewing their hue
+    Agx,
+}
+
+impl ToneMapper {
+    pub const ALL: [ToneMapper; 5] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::ExtendedReinhard,
+        ToneMapper::Aces,
+        ToneMapper::Agx,
+    ];
+}
+
+impl fmt::Display for ToneMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ToneMapper::Clamp => "clamp",
+            ToneMapper::Reinhard => "reinhard",
+            ToneMapper::ExtendedReinhard => "extended-reinhard",
+            ToneMapper::Aces => "aces",
+            ToneMapper::Agx => "agx",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ToneMapper {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<ToneMapper, ()> {
+        ToneMapper::ALL
+            .into_iter()
+            .find(|tone_mapper| tone_mapper.to_string() == name)
+            .ok_or(())
+    }
+}
+
+/// Everything between the film's linear radiance and 8-bit sRGB. Changing it
+/// only changes how the film is shown, the samples stay as they are.
+#[derive(Clone, Copy)]
+pub struct ToneMapping {
+    pub tone_mapper: ToneMapper,
+    // in stops, each one doubles the radiance
+    pub exposure: f64,
+    // radiance that maps to white with the extended Reinhard operator
+    pub white_point: f64,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            tone_mapper: ToneMapper::Clamp,
+            exposure: 0.,
+            white_point: 4.,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn apply(&self, color: Color) -> [u8; 3] {
+        let exposed = 2f64.powf(self.exposure) * color;
+        let exposed = Color(exposed.0.max(0.), exposed.1.max(0.), exposed.2.max(0.));
+
+        let mapped = match self.tone_mapper {
+            ToneMapper::Clamp => exposed,
+            ToneMapper::Reinhard => per_channel(exposed, |x| x / (1. + x)),
+            // Reinhard et al. - "Photographic Tone Reproduction for Digital Images", 2002
+            ToneMapper::ExtendedReinhard => {
+                let white_squared = self.white_point * self.white_point;
+                per_channel(exposed, |x| x * (1. + x / white_squared) / (1. + x))
+            }
+            ToneMapper::Aces => aces(exposed),
+            ToneMapper::Agx => agx(exposed),
+        };
+
+        [mapped.0, mapped.1, mapped.2].map(|channel| {
+            let encoded = linear_to_srgb(channel.clamp(0., 1.));
+            (encoded * 255. + 0.5) as u8
+        })
+    }
+}
+
+fn per_channel(color: Color, map: impl Fn(f64) -> f64) -> Color {
+    Color(map(color.0), map(color.1), map(color.2))
+}
+
+// rows of a 3x3 matrix applied to a column vector
+fn transform(rows: &[[f64; 3]; 3], color: Color) -> Color {
+    let [r, g, b] = rows.map(|row| row[0] * color.0 + row[1] * color.1 + row[2] * color.2);
+    Color(r, g, b)
+}
+
+// the sRGB opto-electronic transfer function, IEC 61966-2-1
+fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+// Hill - "BakingLab", 2016, ACESFitted.hlsl
+fn aces(color: Color) -> Color {
+    // sRGB to the ACES rendering space, with the reference transform's saturation boost
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // back to sRGB, with the output transform's desaturation
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let fitted = per_channel(transform(&INPUT, color), |x| {
+        (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.4329510) + 0.238081)
+    });
+    transform(&OUTPUT, fitted)
+}
+
+// Wrensch - "Minimal AgX Implementation", 2023, a polynomial fit of the base
+// look of Sobotka's AgX
+fn agx(color: Color) -> Color {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+        [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+        [0.0423756549057051, 0.0784336, 0.879142973793104],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+        [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+        [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+    ];
+    // the range of stops around middle grey the curve covers
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let encoded = per_channel(transform(&INSET, color), |x| {
+        let x = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+
+    // the curve's output is meant for a 2.2 gamma display, take it back to linear
+    per_channel(transform(&OUTSET, encoded), |x| x.max(0.).powf(2.2))
+}
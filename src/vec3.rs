@@ -1,8 +1,9 @@
+use std::f64::consts::PI;
 use std::iter::Sum;
 
-use rand::Rng;
+use serde::Deserialize;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Deserialize)]
 pub struct Vec3(pub f64, pub f64, pub f64);
 pub use Vec3 as Point3;
 pub use Vec3 as Color;
@@ -36,26 +37,50 @@ impl Vec3 {
     pub fn length_squared(&self) -> f64 {
         (self.0 * self.0) + (self.1 * self.1) + (self.2 * self.2)
     }
-    pub fn random_in_range(min: f64, max: f64) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        Vec3(
-            rng.gen_range(min..max),
-            rng.gen_range(min..max),
-            rng.gen_range(min..max),
-        )
+    // of a linear sRGB color, Rec. 709 weights
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
     }
+    // Warps of uniform samples from a `Sampler` onto shapes, each one uniform
+    // over the shape's area or volume
 
-    pub fn random_in_unit_sphere() -> Vec3 {
-        let mut vec = Vec3::random_in_range(-1., 1.);
-        while vec.length_squared() >= 1. {
-            vec = Vec3::random_in_range(-1., 1.);
-        }
+    pub fn sample_unit_vector((u, v): (f64, f64)) -> Vec3 {
+        let z = 1. - 2. * u;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * PI * v;
+        Vec3(r * phi.cos(), r * phi.sin(), z)
+    }
 
-        vec
+    // a point of the direction picked by `direction` at a radius that
+    // makes every shell as likely as its volume
+    pub fn sample_in_unit_sphere(direction: (f64, f64), radius: f64) -> Vec3 {
+        radius.cbrt() * Vec3::sample_unit_vector(direction)
     }
 
-    pub fn random_unit_vector() -> Vec3 {
-        Vec3::random_in_unit_sphere().unit()
+    // in the xy plane, keeping nearby samples close together
+    // Shirley and Chiu - "A Low Distortion Map Between Disk and Square", 1997
+    pub fn sample_in_unit_disk((u, v): (f64, f64)) -> Vec3 {
+        let (a, b) = (2. * u - 1., 2. * v - 1.);
+        if a == 0. && b == 0. {
+            return Vec3(0., 0., 0.);
+        }
+        let (r, theta) = match a.abs() > b.abs() {
+            true => (a, PI / 4. * (b / a)),
+            false => (b, PI / 2. - PI / 4. * (a / b)),
+        };
+        Vec3(r * theta.cos(), r * theta.sin(), 0.)
+    }
+
+    // two unit vectors completing an orthonormal basis with this unit vector
+    // Duff et al. - "Building an Orthonormal Basis, Revisited", JCGT 2017
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let sign = 1f64.copysign(self.2);
+        let a = -1. / (sign + self.2);
+        let b = self.0 * self.1 * a;
+        (
+            Vec3(1. + sign * self.0 * self.0 * a, sign * b, -sign * self.0),
+            Vec3(b, sign + self.1 * self.1 * a, -self.1),
+        )
     }
 
     pub fn near_zero(&self) -> bool {
@@ -114,8 +139,185 @@ impl std::ops::Div<f64> for Vec3 {
     }
 }
 
+impl std::ops::Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.0,
+            1 => &self.1,
+            2 => &self.2,
+            _ => panic!("Vec3 axis out of range: {}", axis),
+        }
+    }
+}
+
 impl Sum for Vec3 {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
         iter.fold(Self(0., 0., 0.), |acc, v| acc + v)
     }
 }
+
+// Transforms
+
+/// Row-major 4x4 matrix acting on column vectors, the bottom row is always
+/// (0, 0, 0, 1) for the affine transforms built here.
+#[derive(Clone, Copy, Debug)]
+pub struct Mat4(pub [[f64; 4]; 4]);
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.;
+        }
+        Mat4(m)
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.0[j][i];
+            }
+        }
+        Mat4(m)
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.0;
+        Point3(
+            m[0][0] * p.0 + m[0][1] * p.1 + m[0][2] * p.2 + m[0][3],
+            m[1][0] * p.0 + m[1][1] * p.1 + m[1][2] * p.2 + m[1][3],
+            m[2][0] * p.0 + m[2][1] * p.1 + m[2][2] * p.2 + m[2][3],
+        )
+    }
+
+    // ignores the translation
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.0;
+        Vec3(
+            m[0][0] * v.0 + m[0][1] * v.1 + m[0][2] * v.2,
+            m[1][0] * v.0 + m[1][1] * v.1 + m[1][2] * v.2,
+            m[2][0] * v.0 + m[2][1] * v.1 + m[2][2] * v.2,
+        )
+    }
+}
+
+impl std::ops::Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Mat4(m)
+    }
+}
+
+/// An affine transform along with its inverse, both built up exactly from
+/// translations, rotations and scales so nothing ever has to be inverted.
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    pub fn translate(offset: Vec3) -> Transform {
+        let (mut matrix, mut inverse) = (Mat4::identity(), Mat4::identity());
+        for axis in 0..3 {
+            matrix.0[axis][3] = offset[axis];
+            inverse.0[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    // factors must be non-zero
+    pub fn scale(factors: Vec3) -> Transform {
+        let (mut matrix, mut inverse) = (Mat4::identity(), Mat4::identity());
+        for axis in 0..3 {
+            matrix.0[axis][axis] = factors[axis];
+            inverse.0[axis][axis] = 1. / factors[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    // counterclockwise looking down `axis`, Rodrigues' rotation formula
+    pub fn rotate(axis: Vec3, degrees: f64) -> Transform {
+        let Vec3(x, y, z) = axis.unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1. - cos;
+        let matrix = Mat4([
+            [
+                cos + x * x * k,
+                x * y * k - z * sin,
+                x * z * k + y * sin,
+                0.,
+            ],
+            [
+                y * x * k + z * sin,
+                cos + y * y * k,
+                y * z * k - x * sin,
+                0.,
+            ],
+            [
+                z * x * k - y * sin,
+                z * y * k + x * sin,
+                cos + z * z * k,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ]);
+        // rotations are orthogonal
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    // `self` first, then `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn point(&self, p: &Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    // normals transform with the inverse transpose to stay perpendicular, not unit length
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    // true when the transform only rotates, translates and scales uniformly,
+    // the transforms that keep angles intact
+    pub fn preserves_angles(&self) -> bool {
+        let columns = [0, 1, 2].map(|j| {
+            Vec3(
+                self.matrix.0[0][j],
+                self.matrix.0[1][j],
+                self.matrix.0[2][j],
+            )
+        });
+        let scale_squared = columns[0].length_squared();
+        let tolerance = 1e-9 * scale_squared;
+
+        columns[0].dot(&columns[1]).abs() < tolerance
+            && columns[0].dot(&columns[2]).abs() < tolerance
+            && columns[1].dot(&columns[2]).abs() < tolerance
+            && (columns[1].length_squared() - scale_squared).abs() < tolerance
+            && (columns[2].length_squared() - scale_squared).abs() < tolerance
+    }
+}
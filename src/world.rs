@@ -1,12 +1,17 @@
-use rand::prelude::SmallRng;
-use rand::Rng;
-
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::environment::Environment;
 use crate::ray::Ray;
-use crate::vec3::{Color, Point3, Vec3};
-use std::rc::Rc;
+use crate::roots::{solve_quadratic, solve_quartic};
+use crate::sampler::Sampler;
+use crate::texture::Texture;
+use crate::vec3::{Color, Point3, Transform, Vec3};
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 // TODO: rename Hittable
 
+#[derive(PartialEq)]
 pub enum FaceKind {
     Front,
     Back,
@@ -17,7 +22,9 @@ pub struct HitRecord {
     pub hit_point: Point3,
     pub normal: Vec3,
     pub face: FaceKind,
-    pub material: Rc<dyn Material>,
+    // surface parameterization, (0, 0) for primitives without one
+    pub uv: (f64, f64),
+    pub material: Arc<dyn Material>,
 }
 
 impl HitRecord {
@@ -26,7 +33,7 @@ impl HitRecord {
         t: f64,
         hit_point: Point3,
         outward_normal: Vec3,
-        material: Rc<dyn Material>,
+        material: Arc<dyn Material>,
     ) -> HitRecord {
         let face = match ray.direction.dot(&outward_normal) < 0. {
             true => FaceKind::Front,
@@ -42,6 +49,7 @@ impl HitRecord {
             hit_point,
             face,
             normal: normal_multiplier * outward_normal,
+            uv: (0., 0.),
             material,
         }
     }
@@ -50,79 +58,1072 @@ impl HitRecord {
 // World
 
 pub struct World {
-    // pub objects: Vec<&'a dyn Hittable>,
-    pub objects: Vec<Box<dyn Hittable>>,
+    objects: Bvh,
+    // emissive objects that also appear in `objects`, sampled directly
+    lights: Vec<Arc<dyn Hittable>>,
+    environment: Arc<dyn Environment>,
 }
 
 impl World {
-    pub fn add(&mut self, object: Box<dyn Hittable>) {
-        self.objects.push(object);
+    pub fn new(
+        objects: Vec<Arc<dyn Hittable>>,
+        lights: Vec<Arc<dyn Hittable>>,
+        environment: Arc<dyn Environment>,
+    ) -> World {
+        World {
+            objects: Bvh::new(objects),
+            lights,
+            environment,
+        }
     }
 
     pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut hit_record: Option<HitRecord> = None;
-        let mut closes_so_far = t_max;
-
-        for object in &self.objects {
-            match object.as_ref().hit(ray, t_min, closes_so_far) {
-                None => (),
-                Some(rec) => {
-                    closes_so_far = rec.t;
-                    hit_record = Some(rec);
-                }
+        self.objects.hit(ray, t_min, t_max)
+    }
+
+    // radiance reaching a ray that hits nothing
+    pub fn environment(&self, direction: &Vec3) -> Color {
+        self.environment.radiance(direction)
+    }
+
+    pub fn set_environment(&mut self, environment: Arc<dyn Environment>) {
+        self.environment = environment;
+    }
+
+    // the lights, then the environment if it gets sampled too
+    fn strategy_count(&self) -> usize {
+        self.lights.len() + self.environment.is_sampled() as usize
+    }
+
+    // picks one light (or the environment) uniformly and a direction towards it,
+    // along with the pdf of that direction over all of them, the same value
+    // `light_pdf` gives
+    pub fn sample_light(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<(Vec3, f64)> {
+        let count = self.strategy_count();
+        if count == 0 {
+            return None;
+        }
+        let picked = ((sampler.get_1d() * count as f64) as usize).min(count - 1);
+        let (direction, pdf) = match self.lights.get(picked) {
+            Some(light) => {
+                let sample = light.sample_surface(origin, sampler)?;
+                ((sample.point - *origin).unit(), sample.pdf)
             }
+            None => self.environment.sample(sampler)?,
+        };
+
+        let mut others: f64 = (self.lights.iter().enumerate())
+            .filter(|(idx, _)| *idx != picked)
+            .map(|(_, light)| light.surface_pdf(origin, &direction))
+            .sum();
+        if self.environment.is_sampled() && picked != self.lights.len() {
+            others += self.environment.pdf(&direction);
         }
 
-        hit_record
+        Some((direction, (pdf + others) / count as f64))
     }
 
-    pub fn clear(&mut self) {
-        self.objects.clear();
+    // solid angle pdf of `sample_light` returning `direction`
+    pub fn light_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let count = self.strategy_count();
+        if count == 0 {
+            return 0.;
+        }
+        let mut pdf_sum: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.surface_pdf(origin, direction))
+            .sum();
+        if self.environment.is_sampled() {
+            pdf_sum += self.environment.pdf(direction);
+        }
+
+        pdf_sum / count as f64
     }
 }
 
 // Objects
 
-pub trait Hittable {
+/// A point picked on a surface for light sampling. `pdf` is with respect to
+/// solid angle as seen from the point the sample was taken for.
+pub struct SurfaceSample {
+    pub point: Point3,
+    pub pdf: f64,
+}
+
+pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn bounding_box(&self) -> Aabb;
+
+    // only objects that can act as lights need to support sampling
+    fn sample_surface(
+        &self,
+        _origin: &Point3,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<SurfaceSample> {
+        None
+    }
+
+    // density of `sample_surface` producing `direction` from `origin`,
+    // summed over every point where that direction crosses the surface
+    fn surface_pdf(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.
+    }
+
+    // closed objects bound a volume and can answer `intervals`, which CSG needs
+    fn is_solid(&self) -> bool {
+        false
+    }
+
+    // every stretch of the whole line through `ray` that lies inside the
+    // object, in order along the ray, t can be negative
+    fn intervals(&self, _ray: &Ray) -> Vec<Interval> {
+        vec![]
+    }
+}
+
+/// Where a ray passes through a solid, `enter` is a front face and `exit` a back face.
+pub struct Interval {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
+// pairs up the crossings of a closed surface, nested crossings are merged by
+// counting how deep inside it is
+pub fn intervals_from_crossings(mut crossings: Vec<HitRecord>) -> Vec<Interval> {
+    crossings.sort_by(|a, b| a.t.total_cmp(&b.t));
+    // a ray through a shared edge or vertex crosses every triangle around it,
+    // but only enters or leaves once
+    crossings.dedup_by(|crossing, previous| {
+        crossing.face == previous.face
+            && (crossing.t - previous.t).abs() <= 1e-9 * (1. + previous.t.abs())
+    });
+
+    let mut intervals = vec![];
+    let mut depth = 0;
+    let mut enter = None;
+    for crossing in crossings {
+        match crossing.face {
+            FaceKind::Front => {
+                depth += 1;
+                if depth == 1 {
+                    enter = Some(crossing);
+                }
+            }
+            FaceKind::Back => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(enter) = enter.take() {
+                        intervals.push(Interval {
+                            enter,
+                            exit: crossing,
+                        });
+                    }
+                }
+                depth = depth.max(0);
+            }
+        }
+    }
+
+    intervals
+}
+
+// converts an area density at `point` into a solid angle density seen from `origin`
+pub fn area_pdf_to_solid_angle(pdf: f64, origin: &Point3, point: &Point3, normal: &Vec3) -> f64 {
+    let to_point = *point - *origin;
+    let cosine = normal.dot(&to_point.unit()).abs();
+    if cosine < 1e-12 {
+        return 0.;
+    }
+
+    pdf * to_point.length_squared() / cosine
+}
+
+// u goes around the y axis starting at -x, v from the bottom pole to the top
+pub fn sphere_uv(unit_point: &Vec3) -> (f64, f64) {
+    let theta = (-unit_point.y()).clamp(-1., 1.).acos();
+    let phi = (-unit_point.z()).atan2(unit_point.x()) + PI;
+    (phi / (2. * PI), theta / PI)
 }
 
 pub struct Sphere {
     pub center: Point3,
     pub radius: f64,
-    pub material: Rc<dyn Material>,
+    pub material: Arc<dyn Material>,
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let origin_minus_center = ray.origin - self.center;
-        let a = ray.direction.dot(&ray.direction);
-        let b = 2. * ray.direction.dot(&origin_minus_center);
-        let c = origin_minus_center.dot(&origin_minus_center) - self.radius * self.radius;
+        hit_sphere(self.center, self.radius, &self.material, ray, t_min, t_max)
+    }
 
-        let discriminant = b * b - 4. * a * c;
-        if discriminant < 0. {
-            return None;
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vec3(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        sphere_intervals(self.center, self.radius, &self.material, ray)
+    }
+
+    // uniform over the cone of directions the sphere covers, or over its
+    // whole surface when `origin` is inside
+    fn sample_surface(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared {
+            let normal = Vec3::sample_unit_vector(sampler.get_2d());
+            let point = self.center + self.radius * normal;
+            let pdf = 1. / (4. * PI * radius_squared);
+            return Some(SurfaceSample {
+                point,
+                pdf: area_pdf_to_solid_angle(pdf, origin, &point, &normal),
+            });
+        }
+
+        let (u, v) = sampler.get_2d();
+        let cos_theta_max = (1. - radius_squared / distance_squared).sqrt();
+        let cos_theta = 1. + u * (cos_theta_max - 1.);
+        let sin_theta_squared = 1. - cos_theta * cos_theta;
+        let phi = 2. * PI * v;
+
+        let w = to_center.unit();
+        let (u, v) = w.orthonormal_basis();
+        let direction = sin_theta_squared.sqrt() * (phi.cos() * u + phi.sin() * v) + cos_theta * w;
+        // nearer of the two points the direction crosses the sphere at
+        let distance = distance_squared.sqrt() * cos_theta
+            - (radius_squared - distance_squared * sin_theta_squared)
+                .max(0.)
+                .sqrt();
+
+        Some(SurfaceSample {
+            point: *origin + distance * direction,
+            pdf: 1. / (2. * PI * (1. - cos_theta_max)),
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+            time: 0.,
+        };
+        let hit_record = match self.hit(&ray, 0.001, f64::INFINITY) {
+            Some(hit_record) => hit_record,
+            None => return 0.,
+        };
+
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            let pdf = 1. / (4. * PI * radius_squared);
+            let normal = (hit_record.hit_point - self.center) / self.radius;
+            return area_pdf_to_solid_angle(pdf, origin, &hit_record.hit_point, &normal);
         }
-        let mut root = (-b - discriminant.sqrt()) / (2. * a);
 
+        let cos_theta_max = (1. - radius_squared / distance_squared).sqrt();
+        1. / (2. * PI * (1. - cos_theta_max))
+    }
+}
+
+fn hit_sphere(
+    center: Point3,
+    radius: f64,
+    material: &Arc<dyn Material>,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    let origin_minus_center = ray.origin - center;
+    let a = ray.direction.dot(&ray.direction);
+    let b = 2. * ray.direction.dot(&origin_minus_center);
+    let c = origin_minus_center.dot(&origin_minus_center) - radius * radius;
+
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    let mut root = (-b - discriminant.sqrt()) / (2. * a);
+
+    if root < t_min || root > t_max {
+        root = (-b + discriminant.sqrt()) / (2. * a);
         if root < t_min || root > t_max {
-            root = (-b + discriminant.sqrt()) / (2. * a);
-            if root < t_min || root > t_max {
-                return None;
-            }
+            return None;
         }
+    }
+
+    let t = root;
+    let hit_point = ray.at(root);
+    let outward_normal = (hit_point - center).unit();
+    let mut hit_record = HitRecord::new(ray, t, hit_point, outward_normal, material.clone());
+    hit_record.uv = sphere_uv(&outward_normal);
+    Some(hit_record)
+}
 
-        let t = root;
-        let hit_point = ray.at(root);
-        let outward_normal = (hit_point - self.center).unit();
-        Some(HitRecord::new(
+fn sphere_intervals(
+    center: Point3,
+    radius: f64,
+    material: &Arc<dyn Material>,
+    ray: &Ray,
+) -> Vec<Interval> {
+    let origin_minus_center = ray.origin - center;
+    let a = ray.direction.dot(&ray.direction);
+    let b = 2. * ray.direction.dot(&origin_minus_center);
+    let c = origin_minus_center.dot(&origin_minus_center) - radius * radius;
+
+    let discriminant = b * b - 4. * a * c;
+    if discriminant <= 0. {
+        return vec![];
+    }
+    let crossing = |t: f64| {
+        let hit_point = ray.at(t);
+        let outward_normal = (hit_point - center).unit();
+        let mut hit_record = HitRecord::new(ray, t, hit_point, outward_normal, material.clone());
+        hit_record.uv = sphere_uv(&outward_normal);
+        hit_record
+    };
+
+    vec![Interval {
+        enter: crossing((-b - discriminant.sqrt()) / (2. * a)),
+        exit: crossing((-b + discriminant.sqrt()) / (2. * a)),
+    }]
+}
+
+// moves in a straight line from `center0` at `time0` to `center1` at `time1`,
+// resting at either end outside that interval
+pub struct MovingSphere {
+    pub center0: Point3,
+    pub center1: Point3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let fraction = ((time - self.time0) / (self.time1 - self.time0)).clamp(0., 1.);
+        self.center0 + fraction * (self.center1 - self.center0)
+    }
+}
+
+// not sampled as a light, `sample_surface` has no ray time to place it with
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_sphere(
+            self.center(ray.time),
+            self.radius,
+            &self.material,
             ray,
-            t,
-            hit_point,
-            outward_normal,
-            self.material.clone(),
-        ))
+            t_min,
+            t_max,
+        )
+    }
+
+    // everywhere the sphere passes through
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vec3(self.radius, self.radius, self.radius);
+        Aabb::new(self.center0 - extent, self.center0 + extent)
+            .union(&Aabb::new(self.center1 - extent, self.center1 + extent))
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        sphere_intervals(self.center(ray.time), self.radius, &self.material, ray)
+    }
+}
+
+// Closed primitives list their crossings of a ray's whole line, both `hit`
+// and `intervals` are answered from that list
+
+struct Crossing {
+    t: f64,
+    outward_normal: Vec3,
+    uv: (f64, f64),
+}
+
+fn nearest_crossing(
+    crossings: Vec<Crossing>,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    material: &Arc<dyn Material>,
+) -> Option<HitRecord> {
+    let nearest = crossings
+        .into_iter()
+        .filter(|crossing| crossing.t >= t_min && crossing.t <= t_max)
+        .min_by(|a, b| a.t.total_cmp(&b.t))?;
+    Some(crossing_record(nearest, ray, material))
+}
+
+fn crossing_record(crossing: Crossing, ray: &Ray, material: &Arc<dyn Material>) -> HitRecord {
+    let mut hit_record = HitRecord::new(
+        ray,
+        crossing.t,
+        ray.at(crossing.t),
+        crossing.outward_normal,
+        material.clone(),
+    );
+    hit_record.uv = crossing.uv;
+    hit_record
+}
+
+fn crossing_intervals(
+    crossings: Vec<Crossing>,
+    ray: &Ray,
+    material: &Arc<dyn Material>,
+) -> Vec<Interval> {
+    intervals_from_crossings(
+        crossings
+            .into_iter()
+            .map(|crossing| crossing_record(crossing, ray, material))
+            .collect(),
+    )
+}
+
+// coordinates along (u, v, w) of a basis built around the unit vector `w`
+struct Frame {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Frame {
+    fn new(w: Vec3) -> Frame {
+        let (u, v) = w.orthonormal_basis();
+        Frame { u, v, w }
+    }
+
+    fn to_local(&self, vector: &Vec3) -> Vec3 {
+        Vec3(
+            vector.dot(&self.u),
+            vector.dot(&self.v),
+            vector.dot(&self.w),
+        )
+    }
+
+    fn to_world(&self, vector: &Vec3) -> Vec3 {
+        vector.0 * self.u + vector.1 * self.v + vector.2 * self.w
+    }
+
+    // bounds of a disk of `radius` centered on `center` perpendicular to w
+    fn disk_bounds(&self, center: &Point3, radius: f64) -> Aabb {
+        let w = self.w;
+        let extent = Vec3(
+            radius * (1. - w.0 * w.0).max(0.).sqrt(),
+            radius * (1. - w.1 * w.1).max(0.).sqrt(),
+            radius * (1. - w.2 * w.2).max(0.).sqrt(),
+        );
+        Aabb::new(*center - extent, *center + extent)
+    }
+}
+
+// angle around w in [0, 1), starting at u
+fn turn_fraction(local: &Vec3) -> f64 {
+    let phi = local.1.atan2(local.0);
+    (phi + 2. * PI) % (2. * PI) / (2. * PI)
+}
+
+fn hit_in_plane(ray: &Ray, point: &Point3, normal: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+    let denominator = normal.dot(&ray.direction);
+    if denominator == 0. {
+        return None;
+    }
+    let t = normal.dot(&(*point - ray.origin)) / denominator;
+    match t >= t_min && t <= t_max {
+        true => Some(t),
+        false => None,
+    }
+}
+
+// infinite, uv are distances along two directions in the plane
+pub struct Plane {
+    pub point: Point3,
+    pub normal: Vec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_in_plane(ray, &self.point, &self.normal, t_min, t_max)?;
+        let hit_point = ray.at(t);
+        let (u, v) = self.normal.orthonormal_basis();
+        let offset = hit_point - self.point;
+
+        let mut hit_record = HitRecord::new(ray, t, hit_point, self.normal, self.material.clone());
+        hit_record.uv = (offset.dot(&u), offset.dot(&v));
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::infinite()
+    }
+}
+
+// uv are the distance from the center as a fraction of the radius and the angle around it
+pub struct Disk {
+    pub center: Point3,
+    pub normal: Vec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Disk {
+    fn frame(&self) -> Frame {
+        Frame::new(self.normal)
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = hit_in_plane(ray, &self.center, &self.normal, t_min, t_max)?;
+        let hit_point = ray.at(t);
+        let local = self.frame().to_local(&(hit_point - self.center));
+        let distance = local.length();
+        if distance > self.radius {
+            return None;
+        }
+
+        let mut hit_record = HitRecord::new(ray, t, hit_point, self.normal, self.material.clone());
+        hit_record.uv = (distance / self.radius, turn_fraction(&local));
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.frame().disk_bounds(&self.center, self.radius)
+    }
+
+    // uniform over the area
+    fn sample_surface(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+        let offset = self.radius * Vec3::sample_in_unit_disk(sampler.get_2d());
+        let point = self.center + self.frame().to_world(&offset);
+        let pdf = 1. / (PI * self.radius * self.radius);
+        Some(SurfaceSample {
+            point,
+            pdf: area_pdf_to_solid_angle(pdf, origin, &point, &self.normal),
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+            time: 0.,
+        };
+        match self.hit(&ray, 0.001, f64::INFINITY) {
+            Some(hit_record) => area_pdf_to_solid_angle(
+                1. / (PI * self.radius * self.radius),
+                origin,
+                &hit_record.hit_point,
+                &self.normal,
+            ),
+            None => 0.,
+        }
+    }
+}
+
+/// Rectangle perpendicular to the `axis`-th coordinate axis, facing the
+/// positive or negative direction of it. `min` and `max` are opposite
+/// corners and share their `axis` coordinate.
+pub struct Rect {
+    pub axis: usize,
+    pub facing: f64,
+    pub min: Point3,
+    pub max: Point3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Rect {
+    fn normal(&self) -> Vec3 {
+        let mut normal = [0.; 3];
+        normal[self.axis] = self.facing;
+        Vec3(normal[0], normal[1], normal[2])
+    }
+
+    // the two in-plane axes, in cyclic order after `axis`
+    fn plane_axes(&self) -> (usize, usize) {
+        ((self.axis + 1) % 3, (self.axis + 2) % 3)
+    }
+
+    fn area(&self) -> f64 {
+        let (a, b) = self.plane_axes();
+        (self.max[a] - self.min[a]) * (self.max[b] - self.min[b])
+    }
+}
+
+impl Hittable for Rect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = (self.min[self.axis] - ray.origin[self.axis]) / ray.direction[self.axis];
+        if !(t >= t_min && t <= t_max) {
+            return None;
+        }
+        let hit_point = ray.at(t);
+        let (a, b) = self.plane_axes();
+        let inside =
+            |axis: usize| hit_point[axis] >= self.min[axis] && hit_point[axis] <= self.max[axis];
+        if !inside(a) || !inside(b) {
+            return None;
+        }
+
+        let mut hit_record =
+            HitRecord::new(ray, t, hit_point, self.normal(), self.material.clone());
+        hit_record.uv = (
+            (hit_point[a] - self.min[a]) / (self.max[a] - self.min[a]),
+            (hit_point[b] - self.min[b]) / (self.max[b] - self.min[b]),
+        );
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+
+    // uniform over the area
+    fn sample_surface(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+        let (u, v) = sampler.get_2d();
+        let (a, b) = self.plane_axes();
+        let mut point = [0.; 3];
+        point[self.axis] = self.min[self.axis];
+        point[a] = self.min[a] + u * (self.max[a] - self.min[a]);
+        point[b] = self.min[b] + v * (self.max[b] - self.min[b]);
+        let point = Point3(point[0], point[1], point[2]);
+
+        Some(SurfaceSample {
+            point,
+            pdf: area_pdf_to_solid_angle(1. / self.area(), origin, &point, &self.normal()),
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+            time: 0.,
+        };
+        match self.hit(&ray, 0.001, f64::INFINITY) {
+            Some(hit_record) => area_pdf_to_solid_angle(
+                1. / self.area(),
+                origin,
+                &hit_record.hit_point,
+                &self.normal(),
+            ),
+            None => 0.,
+        }
+    }
+}
+
+// axis-aligned, uv span each face
+pub struct Cuboid {
+    pub min: Point3,
+    pub max: Point3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cuboid {
+    fn crossings(&self, ray: &Ray) -> Vec<Crossing> {
+        // slabs, remembering which axis the ray enters and leaves through
+        let (mut t_enter, mut t_exit) = (f64::NEG_INFINITY, f64::INFINITY);
+        let (mut enter_axis, mut exit_axis) = (0, 0);
+        for axis in 0..3 {
+            let inv_direction = 1. / ray.direction[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_direction;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_direction;
+            if t0.is_nan() || t1.is_nan() {
+                // in the plane of a face and parallel to it
+                continue;
+            }
+            if t0.min(t1) > t_enter {
+                t_enter = t0.min(t1);
+                enter_axis = axis;
+            }
+            if t0.max(t1) < t_exit {
+                t_exit = t0.max(t1);
+                exit_axis = axis;
+            }
+        }
+        if t_enter > t_exit || !t_enter.is_finite() || !t_exit.is_finite() {
+            return vec![];
+        }
+
+        [(t_enter, enter_axis), (t_exit, exit_axis)]
+            .into_iter()
+            .map(|(t, axis)| {
+                let point = ray.at(t);
+                let center = (self.min[axis] + self.max[axis]) / 2.;
+                let mut normal = [0.; 3];
+                normal[axis] = if point[axis] > center { 1. } else { -1. };
+                let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+                Crossing {
+                    t,
+                    outward_normal: Vec3(normal[0], normal[1], normal[2]),
+                    uv: (
+                        (point[a] - self.min[a]) / (self.max[a] - self.min[a]),
+                        (point[b] - self.min[b]) / (self.max[b] - self.min[b]),
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        nearest_crossing(self.crossings(ray), ray, t_min, t_max, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        crossing_intervals(self.crossings(ray), ray, &self.material)
+    }
+}
+
+// capped, uv go around and up the side, the caps are mapped flat
+pub struct Cylinder {
+    pub base: Point3,
+    pub top: Point3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    fn crossings(&self, ray: &Ray) -> Vec<Crossing> {
+        let axis = self.top - self.base;
+        let height = axis.length();
+        let frame = Frame::new(axis / height);
+        let o = frame.to_local(&(ray.origin - self.base));
+        let d = frame.to_local(&ray.direction);
+
+        let side = solve_quadratic(
+            d.0 * d.0 + d.1 * d.1,
+            2. * (o.0 * d.0 + o.1 * d.1),
+            o.0 * o.0 + o.1 * o.1 - self.radius * self.radius,
+        )
+        .into_iter()
+        .filter_map(|t| {
+            let p = o + t * d;
+            (p.2 >= 0. && p.2 <= height).then(|| Crossing {
+                t,
+                outward_normal: frame.to_world(&Vec3(p.0, p.1, 0.)) / self.radius,
+                uv: (turn_fraction(&p), p.2 / height),
+            })
+        });
+
+        let caps = [(0., -1.), (height, 1.)]
+            .into_iter()
+            .filter_map(|(h, facing)| {
+                let t = (h - o.2) / d.2;
+                let p = o + t * d;
+                (t.is_finite() && p.0 * p.0 + p.1 * p.1 <= self.radius * self.radius).then(|| {
+                    Crossing {
+                        t,
+                        outward_normal: facing * frame.w,
+                        uv: (
+                            0.5 + p.0 / (2. * self.radius),
+                            0.5 + p.1 / (2. * self.radius),
+                        ),
+                    }
+                })
+            });
+
+        side.chain(caps).collect()
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        nearest_crossing(self.crossings(ray), ray, t_min, t_max, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let frame = Frame::new((self.top - self.base).unit());
+        frame
+            .disk_bounds(&self.base, self.radius)
+            .union(&frame.disk_bounds(&self.top, self.radius))
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        crossing_intervals(self.crossings(ray), ray, &self.material)
+    }
+}
+
+// circular base of `radius` around `base`, narrowing to a point at `apex`,
+// uv like the cylinder
+pub struct Cone {
+    pub base: Point3,
+    pub apex: Point3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    fn crossings(&self, ray: &Ray) -> Vec<Crossing> {
+        let axis = self.apex - self.base;
+        let height = axis.length();
+        let frame = Frame::new(axis / height);
+        let o = frame.to_local(&(ray.origin - self.base));
+        let d = frame.to_local(&ray.direction);
+
+        // x² + y² = (k (height - z))², the radius shrinks by k per unit of height
+        let k = self.radius / height;
+        let k2 = k * k;
+        let m = height - o.2;
+        let side = solve_quadratic(
+            d.0 * d.0 + d.1 * d.1 - k2 * d.2 * d.2,
+            2. * (o.0 * d.0 + o.1 * d.1 + k2 * m * d.2),
+            o.0 * o.0 + o.1 * o.1 - k2 * m * m,
+        )
+        .into_iter()
+        .filter_map(|t| {
+            let p = o + t * d;
+            (p.2 >= 0. && p.2 <= height).then(|| Crossing {
+                t,
+                outward_normal: frame.to_world(&Vec3(p.0, p.1, k2 * (height - p.2))).unit(),
+                uv: (turn_fraction(&p), p.2 / height),
+            })
+        });
+
+        let t = -o.2 / d.2;
+        let p = o + t * d;
+        let base =
+            (t.is_finite() && p.0 * p.0 + p.1 * p.1 <= self.radius * self.radius).then(|| {
+                Crossing {
+                    t,
+                    outward_normal: -1. * frame.w,
+                    uv: (
+                        0.5 + p.0 / (2. * self.radius),
+                        0.5 + p.1 / (2. * self.radius),
+                    ),
+                }
+            });
+
+        side.chain(base).collect()
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        nearest_crossing(self.crossings(ray), ray, t_min, t_max, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let frame = Frame::new((self.apex - self.base).unit());
+        frame
+            .disk_bounds(&self.base, self.radius)
+            .include(self.apex)
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        crossing_intervals(self.crossings(ray), ray, &self.material)
+    }
+}
+
+// a tube of `minor_radius` around a circle of `major_radius` perpendicular to
+// `axis`, u goes around the axis and v around the tube
+pub struct Torus {
+    pub center: Point3,
+    pub axis: Vec3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Torus {
+    fn crossings(&self, ray: &Ray) -> Vec<Crossing> {
+        let frame = Frame::new(self.axis);
+        let length = ray.direction.length();
+        let d = frame.to_local(&ray.direction) / length;
+        let o = frame.to_local(&(ray.origin - self.center));
+
+        // solve from the point on the ray closest to the center, which keeps
+        // the coefficients small however far away the ray starts
+        let shift = -o.dot(&d);
+        let o = o + shift * d;
+
+        // (|p|² + R² - r²)² = 4 R² (x² + y²) along p = o + s d, |d| = 1
+        let (big_r2, small_r2) = (
+            self.major_radius * self.major_radius,
+            self.minor_radius * self.minor_radius,
+        );
+        let e = o.dot(&o) + big_r2 - small_r2;
+        let f = o.dot(&d);
+        let roots = solve_quartic(
+            4. * f,
+            4. * f * f + 2. * e - 4. * big_r2 * (d.0 * d.0 + d.1 * d.1),
+            4. * f * e - 8. * big_r2 * (o.0 * d.0 + o.1 * d.1),
+            e * e - 4. * big_r2 * (o.0 * o.0 + o.1 * o.1),
+        );
+
+        roots
+            .into_iter()
+            .map(|s| {
+                let p = o + s * d;
+                // nearest point on the center circle, the normal points away from it
+                let ring = Vec3(p.0, p.1, 0.);
+                let ring = match ring.near_zero() {
+                    true => Vec3(self.major_radius, 0., 0.),
+                    false => self.major_radius * ring.unit(),
+                };
+                let tube = p - ring;
+                let tube_angle = tube.2.atan2(tube.dot(&ring.unit()));
+                Crossing {
+                    t: (s + shift) / length,
+                    outward_normal: frame.to_world(&tube.unit()),
+                    uv: (
+                        turn_fraction(&p),
+                        (tube_angle + 2. * PI) % (2. * PI) / (2. * PI),
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        nearest_crossing(self.crossings(ray), ray, t_min, t_max, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let frame = Frame::new(self.axis);
+        let tube = Vec3(self.minor_radius, self.minor_radius, self.minor_radius);
+        let ring = frame.disk_bounds(&self.center, self.major_radius);
+        Aabb::new(ring.min - tube, ring.max + tube)
+    }
+
+    fn is_solid(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        crossing_intervals(self.crossings(ray), ray, &self.material)
+    }
+}
+
+/// Places a shared object in the world through a transform, so one mesh can
+/// appear many times while its triangles and BVH exist only once.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    bounding_box: Aabb,
+    // solid angle densities only carry over when angles do
+    preserves_angles: bool,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        let local_box = object.bounding_box();
+        let bounding_box = match local_box.is_finite() {
+            true => (0..8)
+                .map(|corner| {
+                    let pick = |axis: usize| match (corner >> axis) & 1 {
+                        0 => local_box.min[axis],
+                        _ => local_box.max[axis],
+                    };
+                    transform.point(&Point3(pick(0), pick(1), pick(2)))
+                })
+                .fold(Aabb::empty(), |acc, point| acc.include(point)),
+            false => Aabb::infinite(),
+        };
+
+        Instance {
+            object,
+            bounding_box,
+            preserves_angles: transform.preserves_angles(),
+            transform,
+        }
+    }
+
+    fn to_world(&self, mut hit_record: HitRecord) -> HitRecord {
+        hit_record.hit_point = self.transform.point(&hit_record.hit_point);
+        // still on the side the ray came from, the inverse transpose keeps signs of dot products
+        hit_record.normal = self.transform.normal(&hit_record.normal).unit();
+        hit_record
+    }
+
+    fn to_local(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.transform.inverse.transform_point(&ray.origin),
+            direction: self.transform.inverse.transform_vector(&ray.direction),
+            time: ray.time,
+        }
+    }
+}
+
+// rays are mapped into object space unnormalized, so t is the same in both spaces
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let hit_record = self.object.hit(&self.to_local(ray), t_min, t_max)?;
+        Some(self.to_world(hit_record))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    fn sample_surface(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<SurfaceSample> {
+        if !self.preserves_angles {
+            return None;
+        }
+        let local_origin = self.transform.inverse.transform_point(origin);
+        let sample = self.object.sample_surface(&local_origin, sampler)?;
+        Some(SurfaceSample {
+            point: self.transform.point(&sample.point),
+            pdf: sample.pdf,
+        })
+    }
+
+    fn surface_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if !self.preserves_angles {
+            return 0.;
+        }
+        let local = self.to_local(&Ray {
+            origin: *origin,
+            direction: *direction,
+            time: 0.,
+        });
+        self.object.surface_pdf(&local.origin, &local.direction)
+    }
+
+    fn is_solid(&self) -> bool {
+        self.object.is_solid()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        self.object
+            .intervals(&self.to_local(ray))
+            .into_iter()
+            .map(|interval| Interval {
+                enter: self.to_world(interval.enter),
+                exit: self.to_world(interval.exit),
+            })
+            .collect()
     }
 }
 
@@ -130,75 +1131,176 @@ impl Hittable for Sphere {
 
 pub struct ScatterRecord {
     pub ray: Ray,
+    // bsdf times cosine over pdf, what the light along `ray` gets scaled by
     pub attenuation: Color,
+    // perfect mirrors and glass can't be lit by sampling lights directly
+    pub is_specular: bool,
+    // solid angle density of `ray.direction`, unused when specular
+    pub pdf: f64,
 }
 
-pub trait Material {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord>;
+pub trait Material: Send + Sync {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord>;
+
+    // radiance given off by the surface itself, black for anything but lights
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color(0., 0., 0.)
+    }
+
+    // bsdf times cosine for light arriving from `direction`,
+    // only called for non-specular scattering
+    fn eval(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Color {
+        Color(0., 0., 0.)
+    }
+
+    // density of `scatter` picking `direction`, only called for non-specular scattering
+    fn pdf(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        0.
+    }
 }
 
 pub struct Lambertian {
-    pub albedo: Color,
+    pub albedo: Arc<dyn Texture>,
 }
 
 pub struct Metal {
-    pub albedo: Color,
+    pub albedo: Arc<dyn Texture>,
     pub fuzz: f64,
 }
 
 pub struct Dielectric {
     pub ior: f64,
-    pub rng: SmallRng,
+}
+
+// emits from the front face only and absorbs everything that reaches it
+pub struct DiffuseLight {
+    pub emit: Color,
+}
+
+// scatters equally in every direction, the phase function of a `ConstantMedium`
+pub struct Isotropic {
+    pub albedo: Arc<dyn Texture>,
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let mut scatter_direction = hit_record.normal + Vec3::sample_unit_vector(sampler.get_2d());
 
         if scatter_direction.near_zero() {
             scatter_direction = hit_record.normal;
         }
 
         Some(ScatterRecord {
-            attenuation: self.albedo,
+            attenuation: self.albedo.value(hit_record.uv, &hit_record.hit_point),
             ray: Ray {
                 origin: hit_record.hit_point,
                 direction: scatter_direction,
+                time: ray.time,
             },
+            is_specular: false,
+            pdf: self.pdf(ray, hit_record, &scatter_direction),
         })
     }
+
+    fn eval(&self, _ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        let cosine = hit_record.normal.dot(&direction.unit()).max(0.);
+        self.albedo.value(hit_record.uv, &hit_record.hit_point) * (cosine / PI)
+    }
+
+    // normal + random unit vector is cosine distributed
+    fn pdf(&self, _ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        hit_record.normal.dot(&direction.unit()).max(0.) / PI
+    }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
-        let scatter_direction = ray.direction.unit()
-            - 2. * ray.direction.unit().dot(&hit_record.normal) * hit_record.normal;
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let scatter_direction = self.reflect(ray, hit_record);
+        let fuzz = Vec3::sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d());
         let scattered_ray = Ray {
             origin: hit_record.hit_point,
-            direction: scatter_direction + self.fuzz * Vec3::random_in_unit_sphere(),
+            direction: scatter_direction + self.fuzz * fuzz,
+            time: ray.time,
         };
 
         // hack: absorb the rays that leak inside after applying fuzz
         if scattered_ray.direction.dot(&hit_record.normal) > 0. {
             Some(ScatterRecord {
+                pdf: self.pdf(ray, hit_record, &scattered_ray.direction),
                 ray: scattered_ray,
-                attenuation: self.albedo,
+                attenuation: self.albedo.value(hit_record.uv, &hit_record.hit_point),
+                is_specular: self.fuzz == 0.,
             })
         } else {
             None
         }
     }
+
+    // every direction `scatter` keeps is weighted by albedo alone,
+    // so bsdf times cosine has to be albedo times the pdf
+    fn eval(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Color {
+        if direction.dot(&hit_record.normal) <= 0. {
+            return Color(0., 0., 0.);
+        }
+        self.albedo.value(hit_record.uv, &hit_record.hit_point)
+            * self.pdf(ray, hit_record, direction)
+    }
+
+    // `scatter` picks a point uniformly in a ball of radius fuzz around the tip of
+    // the unit reflection vector, a direction's density is the ball volume along it
+    // (the integral of t^2 dt over the chord) divided by the whole ball's volume
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        if self.fuzz == 0. {
+            return 0.;
+        }
+        let reflected = self.reflect(ray, hit_record);
+        let b = direction.unit().dot(&reflected);
+        let discriminant = b * b - (1. - self.fuzz * self.fuzz);
+        if discriminant < 0. {
+            return 0.;
+        }
+
+        let t_near = (b - discriminant.sqrt()).max(0.);
+        let t_far = (b + discriminant.sqrt()).max(0.);
+        (t_far.powi(3) - t_near.powi(3)) / (4. * PI * self.fuzz.powi(3))
+    }
+}
+
+impl Metal {
+    fn reflect(&self, ray: &Ray, hit_record: &HitRecord) -> Vec3 {
+        ray.direction.unit() - 2. * ray.direction.unit().dot(&hit_record.normal) * hit_record.normal
+    }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         let (n1, n2) = match hit_record.face {
             FaceKind::Front => (1., self.ior),
             FaceKind::Back => (self.ior, 1.),
         };
 
         let unit_ray = ray.direction.unit();
-        let normal_projection = (-1.0 * unit_ray.dot(&hit_record.normal)) * hit_record.normal;
+        let normal_projection = -unit_ray.dot(&hit_record.normal) * hit_record.normal;
         let rtn = normal_projection + unit_ray;
         let x_part = rtn * (n1 / n2);
         let y_part = (1.0 - x_part.dot(&x_part)).sqrt() * (-1. * hit_record.normal);
@@ -208,20 +1310,70 @@ impl Material for Dielectric {
             unit_ray - 2. * unit_ray.dot(&hit_record.normal) * hit_record.normal;
         let reflectance = Dielectric::reflectance(normal_projection.length(), n1, n2);
 
-        let mut rng = rand::thread_rng();
         let cannot_refract = x_part.length() > 1.;
         Some(ScatterRecord {
             attenuation: Color(1., 1., 1.),
             ray: Ray {
                 origin: hit_record.hit_point,
-                direction: if cannot_refract || reflectance > rng.gen_range(0.0..1.0) {
+                direction: if cannot_refract || reflectance > sampler.get_1d() {
                     reflect_direction
                 } else {
                     refract_direction
                 },
+                time: ray.time,
+            },
+            is_specular: true,
+            pdf: 0.,
+        })
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _hit_record: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        match hit_record.face {
+            FaceKind::Front => self.emit,
+            FaceKind::Back => Color(0., 0., 0.),
+        }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let scatter_direction = Vec3::sample_unit_vector(sampler.get_2d());
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(hit_record.uv, &hit_record.hit_point),
+            ray: Ray {
+                origin: hit_record.hit_point,
+                direction: scatter_direction,
+                time: ray.time,
             },
+            is_specular: false,
+            pdf: self.pdf(ray, hit_record, &scatter_direction),
         })
     }
+
+    // no cosine term, light is scattered by the volume rather than a surface
+    fn eval(&self, _ray: &Ray, hit_record: &HitRecord, _direction: &Vec3) -> Color {
+        self.albedo.value(hit_record.uv, &hit_record.hit_point) / (4. * PI)
+    }
+
+    fn pdf(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        1. / (4. * PI)
+    }
 }
 
 impl Dielectric {